@@ -0,0 +1,929 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use main::{
+  helper::{oracle::DECIMALS, pubutil::Boolean},
+  processor::Processor,
+  schema::{
+    farm::Farm, lock::Lock, member::Member, pool::Pool, split::Split, staker::Staker,
+    timelock::Timelock, whitelist::Whitelist,
+  },
+};
+use solana_program::{
+  account_info::AccountInfo,
+  clock::Clock,
+  entrypoint::ProgramResult,
+  instruction::Instruction,
+  program::invoke,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack},
+  program_stubs::{set_syscall_stubs, SyscallStubs},
+  pubkey::Pubkey,
+  rent::Rent,
+  system_program, sysvar,
+  sysvar::Sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::{Account, Mint};
+use std::sync::Once;
+
+const RESERVE: u64 = 1000000000;
+const BALANCE: u64 = 1000000000000;
+const LAMPORTS: u64 = 1000000000;
+
+// Account indices in the model
+const PAYER: usize = 0;
+const POOL: usize = 1;
+const LPT: usize = 2;
+const BURN: usize = 3;
+const MINT_LPT: usize = 4;
+const VAULT: usize = 5;
+const PROOF: usize = 6;
+const SRC: [usize; 3] = [7, 8, 9];
+const MINT: [usize; 3] = [10, 11, 12];
+const TREASURY: [usize; 3] = [13, 14, 15];
+const TREASURER: usize = 16;
+const SYSTEM_PROGRAM: usize = 17;
+const SPLT_PROGRAM: usize = 18;
+const SYSVAR_RENT: usize = 19;
+const SPLATA_PROGRAM: usize = 20;
+const SYSVAR_CLOCK: usize = 21;
+const STRANGER: usize = 22;
+const TIMELOCK: [usize; 2] = [23, 24];
+const NEW_OWNER: usize = 25;
+const WHITELIST: usize = 26;
+const SPLIT: usize = 27;
+const FARM: usize = 28;
+const STAKER: usize = 29;
+const TREASURY_LPT: usize = 30;
+const TREASURY_REWARD: usize = 31;
+const LOCK: usize = 32;
+const ESCROW: usize = 33;
+const ENTRY: usize = 34;
+const SIZE: usize = 35;
+
+// Number of instruction tags, MigratePool is left out as reallocating needs accounts
+// serialized by the runtime and the model holds no unversioned pool
+const INSTRUCTIONS: u8 = 38;
+
+// Accounts whose keypairs sign every transaction
+const SIGNERS: [usize; 14] = [
+  PAYER,
+  POOL,
+  VAULT,
+  STRANGER,
+  TIMELOCK[0],
+  TIMELOCK[1],
+  WHITELIST,
+  SPLIT,
+  FARM,
+  STAKER,
+  TREASURY_LPT,
+  TREASURY_REWARD,
+  LOCK,
+  ESCROW,
+];
+
+fn program_id() -> Pubkey {
+  Pubkey::new_from_array([255; 32])
+}
+
+///
+/// Cross-program invocations run SPL Token natively against the model
+///
+struct Stubs;
+impl SyscallStubs for Stubs {
+  fn sol_log(&self, _message: &str) {}
+  fn sol_log_compute_units(&self) {}
+  fn sol_invoke_signed(
+    &self,
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+    signers_seeds: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let mut signers = vec![];
+    for seeds in signers_seeds.iter() {
+      signers.push(Pubkey::create_program_address(seeds, &program_id())?);
+    }
+    let mut accounts = vec![];
+    for meta in instruction.accounts.iter() {
+      let mut info = account_infos
+        .iter()
+        .find(|info| *info.key == meta.pubkey)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?
+        .clone();
+      info.is_signer = info.is_signer || signers.contains(info.key);
+      if meta.is_signer && !info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+      }
+      accounts.push(info);
+    }
+    if instruction.program_id == spl_token::id() {
+      return spl_token::processor::Processor::process(
+        &instruction.program_id,
+        &accounts,
+        &instruction.data,
+      );
+    }
+    if instruction.program_id == spl_associated_token_account::id() {
+      // The model pre-allocates every account, creating an associated account only initializes it
+      let (target, wallet, mint, rent) = (&accounts[1], &accounts[2], &accounts[3], &accounts[6]);
+      if *target.key != get_associated_token_address(wallet.key, mint.key)
+        || target.data.borrow().iter().any(|&byte| byte != 0)
+      {
+        return Err(ProgramError::InvalidArgument);
+      }
+      let ix = spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        target.key,
+        mint.key,
+        wallet.key,
+      )?;
+      return spl_token::processor::Processor::process(
+        &spl_token::id(),
+        &[target.clone(), mint.clone(), wallet.clone(), rent.clone()],
+        &ix.data,
+      );
+    }
+    Err(ProgramError::IncorrectProgramId)
+  }
+}
+
+///
+/// In-memory accounts, reverted when an instruction fails like on chain
+///
+#[derive(Clone)]
+struct Model {
+  keys: Vec<Pubkey>,
+  owners: Vec<Pubkey>,
+  lamports: Vec<u64>,
+  data: Vec<Vec<u8>>,
+}
+
+impl Model {
+  fn new() -> Self {
+    let program_id = program_id();
+    // The pool key must give an off-curve treasurer
+    let (pool, treasurer) = (0..=255)
+      .find_map(|seed| {
+        let pool = Pubkey::new_from_array([seed; 32]);
+        Pubkey::create_program_address(&[&pool.to_bytes()], &program_id)
+          .ok()
+          .map(|treasurer| (pool, treasurer))
+      })
+      .unwrap();
+    let payer = Pubkey::new_from_array([1; 32]);
+    let mint_lpt = Pubkey::new_from_array([2; 32]);
+    let mints = [
+      Pubkey::new_from_array([3; 32]),
+      Pubkey::new_from_array([4; 32]),
+      Pubkey::new_from_array([5; 32]),
+    ];
+
+    let mut keys = vec![Pubkey::default(); SIZE];
+    let mut owners = vec![system_program::id(); SIZE];
+    let mut sizes = vec![0; SIZE];
+    let mut account = |index: usize, key: Pubkey, owner: Pubkey, size: usize| {
+      keys[index] = key;
+      owners[index] = owner;
+      sizes[index] = size;
+    };
+    account(PAYER, payer, system_program::id(), 0);
+    account(POOL, pool, program_id, Pool::LEN);
+    account(
+      LPT,
+      get_associated_token_address(&payer, &mint_lpt),
+      spl_token::id(),
+      Account::LEN,
+    );
+    account(
+      BURN,
+      get_associated_token_address(&treasurer, &mint_lpt),
+      spl_token::id(),
+      Account::LEN,
+    );
+    account(MINT_LPT, mint_lpt, spl_token::id(), Mint::LEN);
+    account(
+      VAULT,
+      Pubkey::new_from_array([6; 32]),
+      spl_token::id(),
+      Account::LEN,
+    );
+    account(
+      PROOF,
+      program_id.xor(&(pool.xor(&treasurer))),
+      system_program::id(),
+      0,
+    );
+    for i in 0..3 {
+      account(
+        SRC[i],
+        Pubkey::new_from_array([7 + i as u8; 32]),
+        spl_token::id(),
+        Account::LEN,
+      );
+      account(MINT[i], mints[i], spl_token::id(), Mint::LEN);
+      account(
+        TREASURY[i],
+        get_associated_token_address(&treasurer, &mints[i]),
+        spl_token::id(),
+        Account::LEN,
+      );
+    }
+    account(TREASURER, treasurer, system_program::id(), 0);
+    account(SYSTEM_PROGRAM, system_program::id(), Pubkey::default(), 0);
+    account(SPLT_PROGRAM, spl_token::id(), Pubkey::default(), 0);
+    account(
+      SYSVAR_RENT,
+      sysvar::rent::id(),
+      sysvar::id(),
+      Rent::size_of(),
+    );
+    account(
+      SPLATA_PROGRAM,
+      spl_associated_token_account::id(),
+      Pubkey::default(),
+      0,
+    );
+    account(
+      SYSVAR_CLOCK,
+      sysvar::clock::id(),
+      sysvar::id(),
+      Clock::size_of(),
+    );
+    account(
+      STRANGER,
+      Pubkey::new_from_array([10; 32]),
+      system_program::id(),
+      0,
+    );
+    for (i, &index) in TIMELOCK.iter().enumerate() {
+      account(
+        index,
+        Pubkey::new_from_array([11 + i as u8; 32]),
+        program_id,
+        Timelock::LEN,
+      );
+    }
+    account(
+      NEW_OWNER,
+      Pubkey::new_from_array([13; 32]),
+      system_program::id(),
+      0,
+    );
+    account(
+      WHITELIST,
+      Pubkey::new_from_array([14; 32]),
+      program_id,
+      Whitelist::LEN,
+    );
+    account(
+      SPLIT,
+      Pubkey::new_from_array([15; 32]),
+      program_id,
+      Split::LEN,
+    );
+    account(
+      FARM,
+      Pubkey::new_from_array([16; 32]),
+      program_id,
+      Farm::LEN,
+    );
+    account(
+      STAKER,
+      Pubkey::new_from_array([17; 32]),
+      program_id,
+      Staker::LEN,
+    );
+    account(
+      TREASURY_LPT,
+      Pubkey::new_from_array([18; 32]),
+      spl_token::id(),
+      Account::LEN,
+    );
+    account(
+      TREASURY_REWARD,
+      Pubkey::new_from_array([19; 32]),
+      spl_token::id(),
+      Account::LEN,
+    );
+    account(
+      LOCK,
+      Pubkey::new_from_array([20; 32]),
+      program_id,
+      Lock::LEN,
+    );
+    account(
+      ESCROW,
+      Pubkey::new_from_array([21; 32]),
+      spl_token::id(),
+      Account::LEN,
+    );
+    // The allowlist entry of the payer, already allocated like the other accounts
+    account(
+      ENTRY,
+      Member::find_address(&pool, &payer, &program_id).0,
+      program_id,
+      Member::LEN,
+    );
+
+    let mut model = Model {
+      keys,
+      owners,
+      lamports: vec![LAMPORTS; SIZE],
+      data: sizes.into_iter().map(|size| vec![0; size]).collect(),
+    };
+    {
+      let mut infos = model.infos();
+      Rent::default()
+        .to_account_info(&mut infos[SYSVAR_RENT])
+        .unwrap();
+      Clock::default()
+        .to_account_info(&mut infos[SYSVAR_CLOCK])
+        .unwrap();
+      // Fund the payer
+      for i in 0..3 {
+        for ix in [
+          spl_token::instruction::initialize_mint(&spl_token::id(), &mints[i], &payer, None, 9),
+          spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            infos[SRC[i]].key,
+            &mints[i],
+            &payer,
+          ),
+          spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &mints[i],
+            infos[SRC[i]].key,
+            &payer,
+            &[],
+            BALANCE,
+          ),
+        ]
+        .iter()
+        {
+          invoke(ix.as_ref().unwrap(), &infos).unwrap();
+        }
+      }
+    }
+    model
+  }
+
+  fn infos(&mut self) -> Vec<AccountInfo<'_>> {
+    self
+      .keys
+      .iter()
+      .zip(self.owners.iter())
+      .zip(self.lamports.iter_mut())
+      .zip(self.data.iter_mut())
+      .enumerate()
+      .map(|(index, (((key, owner), lamports), data))| {
+        AccountInfo::new(
+          key,
+          SIGNERS.contains(&index),
+          true,
+          lamports,
+          data,
+          owner,
+          false,
+          0,
+        )
+      })
+      .collect()
+  }
+
+  // Process one instruction, reverting the accounts when it fails
+  fn process(&mut self, data: &[u8], indices: &[usize]) -> ProgramResult {
+    let snapshot = self.clone();
+    let result = {
+      let infos = self.infos();
+      let accounts: Vec<AccountInfo> = indices.iter().map(|&i| infos[i].clone()).collect();
+      Processor::process(&program_id(), &accounts, data)
+    };
+    if result.is_err() {
+      *self = snapshot;
+    }
+    result
+  }
+
+  fn warp(&mut self, seconds: u32) {
+    let mut infos = self.infos();
+    let mut clock = Clock::from_account_info(&infos[SYSVAR_CLOCK]).unwrap();
+    clock.unix_timestamp += seconds as i64;
+    clock.slot += 1;
+    clock.to_account_info(&mut infos[SYSVAR_CLOCK]).unwrap();
+  }
+
+  // Treasuries must always hold at least the reserves recorded by the pool
+  fn assert_covered(&self) {
+    // Staked LPT stay in the escrow
+    let farm_data = Farm::unpack_unchecked(&self.data[FARM]).unwrap();
+    if farm_data.is_initialized() {
+      let escrow = Account::unpack(&self.data[TREASURY_LPT]).unwrap().amount;
+      assert_eq!(escrow, farm_data.total_stake);
+    }
+    // Locked LPT stay in the escrow until released
+    let lock_data = Lock::unpack_unchecked(&self.data[LOCK]).unwrap();
+    if lock_data.is_initialized() {
+      let escrow = Account::unpack(&self.data[ESCROW]).unwrap().amount;
+      assert!(lock_data.released <= lock_data.amount);
+      assert_eq!(escrow, lock_data.amount - lock_data.released);
+    }
+    let pool_data = Pool::unpack_unchecked(&self.data[POOL]).unwrap();
+    if !pool_data.is_initialized() {
+      return;
+    }
+    for (treasury, reserve) in [
+      (pool_data.treasury_s, pool_data.reserve_s),
+      (pool_data.treasury_a, pool_data.reserve_a),
+      (pool_data.treasury_b, pool_data.reserve_b),
+    ]
+    .iter()
+    {
+      let index = self.keys.iter().position(|key| key == treasury).unwrap();
+      let amount = Account::unpack(&self.data[index])
+        .map(|account| account.amount)
+        .unwrap_or(0);
+      assert!(
+        amount >= *reserve,
+        "treasury {} holds {} for a reserve of {}",
+        treasury,
+        amount,
+        reserve
+      );
+    }
+  }
+}
+
+///
+/// Fuzz input
+///
+#[derive(Arbitrary, Debug)]
+enum Step {
+  // A well-formed instruction with its expected accounts, optionally shuffled
+  Call {
+    tag: u8,
+    args: [u64; 4],
+    small: bool,
+    pick: u8,
+    stranger: bool,
+    swaps: Vec<(u8, u8)>,
+  },
+  // Arbitrary data against arbitrary accounts
+  Raw {
+    data: Vec<u8>,
+    accounts: Vec<u8>,
+  },
+  // Move the clock forward
+  Warp {
+    seconds: u32,
+  },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+  initialize: bool,
+  steps: Vec<Step>,
+}
+
+// Number of u64 arguments and expected accounts of each instruction
+fn layout(tag: u8, pick: u8) -> (usize, Vec<usize>) {
+  let [src_s, src_a, src_b] = SRC;
+  let [treasury_s, treasury_a, treasury_b] = TREASURY;
+  let timelock = TIMELOCK[pick as usize % 2];
+  let liquidity = vec![
+    PAYER,
+    POOL,
+    LPT,
+    MINT_LPT,
+    src_s,
+    treasury_s,
+    src_a,
+    treasury_a,
+    src_b,
+    treasury_b,
+    TREASURER,
+    SPLT_PROGRAM,
+  ];
+  match tag {
+    0 => (
+      3,
+      vec![
+        PAYER,
+        PAYER,
+        POOL,
+        LPT,
+        BURN,
+        MINT_LPT,
+        VAULT,
+        PROOF,
+        src_s,
+        MINT[0],
+        treasury_s,
+        src_a,
+        MINT[1],
+        treasury_a,
+        src_b,
+        MINT[2],
+        treasury_b,
+        TREASURER,
+        SYSTEM_PROGRAM,
+        SPLT_PROGRAM,
+        SYSVAR_RENT,
+        SPLATA_PROGRAM,
+      ],
+    ),
+    1 => {
+      // The allowlist entry is optional
+      let mut accounts = [&liquidity[..], &[SYSVAR_CLOCK]].concat();
+      if pick % 2 == 1 {
+        accounts.push(ENTRY);
+      }
+      (3, accounts)
+    }
+    2 => (1, [&liquidity[..], &[SYSVAR_CLOCK]].concat()),
+    3 => {
+      let bid = pick as usize % 3;
+      let ask = (pick as usize / 3) % 3;
+      let mut accounts = vec![
+        PAYER,
+        POOL,
+        VAULT,
+        SRC[bid],
+        TREASURY[bid],
+        SRC[ask],
+        TREASURY[ask],
+        treasury_s,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ];
+      // The whitelist, the allowlist entry and the referrer are optional, as flagged
+      if pick / 9 % 2 == 1 {
+        accounts.push(WHITELIST);
+      }
+      if pick / 18 % 2 == 1 {
+        accounts.push(ENTRY);
+      }
+      if pick / 36 % 2 == 1 {
+        accounts.push(src_s);
+      }
+      (2, accounts)
+    }
+    4 | 5 => (0, vec![PAYER, POOL]),
+    6 => (1, vec![PAYER, POOL, VAULT, src_s, TREASURER, SPLT_PROGRAM]),
+    7 => (0, vec![PAYER, POOL, NEW_OWNER, timelock, SYSVAR_CLOCK]),
+    8 => {
+      // Actions apply to the pool, the whitelist, the split or the farm
+      let targets = [POOL, WHITELIST, SPLIT, FARM];
+      (
+        0,
+        vec![
+          PAYER,
+          POOL,
+          timelock,
+          SYSVAR_CLOCK,
+          targets[pick as usize / 2 % targets.len()],
+        ],
+      )
+    }
+    9 => (0, vec![PAYER, POOL, timelock]),
+    10 => (
+      0,
+      vec![POOL, treasury_s, treasury_a, treasury_b, SYSVAR_CLOCK],
+    ),
+    11 => (
+      0,
+      vec![
+        PAYER,
+        POOL,
+        treasury_s,
+        src_s,
+        treasury_a,
+        src_a,
+        treasury_b,
+        src_b,
+        TREASURER,
+        SPLT_PROGRAM,
+      ],
+    ),
+    12 => (
+      3,
+      vec![
+        PAYER,
+        POOL,
+        src_s,
+        treasury_s,
+        src_a,
+        treasury_a,
+        src_b,
+        treasury_b,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    13 => (
+      0,
+      vec![
+        PAYER,
+        POOL,
+        MINT_LPT,
+        BURN,
+        VAULT,
+        treasury_s,
+        src_s,
+        treasury_a,
+        src_a,
+        treasury_b,
+        src_b,
+        TREASURER,
+        SPLT_PROGRAM,
+      ],
+    ),
+    14 => (
+      3,
+      vec![
+        PAYER,
+        POOL,
+        LPT,
+        MINT_LPT,
+        BURN,
+        src_s,
+        MINT[0],
+        treasury_s,
+        src_a,
+        MINT[1],
+        treasury_a,
+        src_b,
+        MINT[2],
+        treasury_b,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    15 => (2, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    16 => (0, vec![PAYER, POOL, WHITELIST]),
+    17 => {
+      let trader = [PAYER, STRANGER][pick as usize / 2 % 2];
+      (
+        1,
+        vec![PAYER, POOL, WHITELIST, trader, timelock, SYSVAR_CLOCK],
+      )
+    }
+    18 => (1, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    19 | 20 => {
+      // Up to three SEN accounts share the vault, the last one funds the farm
+      let beneficiaries = &[src_s, VAULT, TREASURY_REWARD][..pick as usize % 3 + 1];
+      let accounts = if tag == 19 {
+        vec![PAYER, POOL, SPLIT, timelock, SYSVAR_CLOCK]
+      } else {
+        vec![POOL, VAULT, SPLIT, TREASURER, SPLT_PROGRAM]
+      };
+      (beneficiaries.len(), [&accounts[..], beneficiaries].concat())
+    }
+    21 => (
+      1,
+      vec![
+        PAYER,
+        POOL,
+        FARM,
+        MINT_LPT,
+        TREASURY_LPT,
+        MINT[0],
+        TREASURY_REWARD,
+        TREASURER,
+        SYSVAR_RENT,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    22 => (1, vec![PAYER, POOL, FARM, timelock, SYSVAR_CLOCK]),
+    23 => (
+      1,
+      vec![
+        PAYER,
+        FARM,
+        STAKER,
+        LPT,
+        TREASURY_LPT,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    24 => (
+      1,
+      vec![
+        PAYER,
+        POOL,
+        FARM,
+        STAKER,
+        TREASURY_LPT,
+        LPT,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    25 => (
+      0,
+      vec![
+        PAYER,
+        POOL,
+        FARM,
+        STAKER,
+        TREASURY_REWARD,
+        src_s,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    26 => (
+      2,
+      vec![
+        PAYER,
+        POOL,
+        LOCK,
+        LPT,
+        MINT_LPT,
+        ESCROW,
+        TREASURER,
+        SYSVAR_RENT,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    27 => (
+      0,
+      vec![
+        PAYER,
+        POOL,
+        LOCK,
+        ESCROW,
+        LPT,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    28 => (0, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    29 => (
+      0,
+      vec![PAYER, POOL, PAYER, ENTRY, SYSTEM_PROGRAM, SYSVAR_RENT],
+    ),
+    30 => (0, vec![PAYER, POOL, ENTRY]),
+    31 => (4, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    32 => (1, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    33 => (2, vec![PAYER, POOL, timelock, SYSVAR_CLOCK]),
+    34 => (0, vec![PAYER, POOL]),
+    35 => {
+      let index = pick as usize % 3;
+      let mut accounts = vec![
+        PAYER,
+        POOL,
+        LPT,
+        MINT_LPT,
+        SRC[index],
+        TREASURY[index],
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ];
+      if pick / 3 % 2 == 1 {
+        accounts.push(ENTRY);
+      }
+      (2, accounts)
+    }
+    // The model holds a single pool, so it migrates into itself
+    36 => (
+      2,
+      vec![
+        PAYER,
+        POOL,
+        LPT,
+        MINT_LPT,
+        treasury_s,
+        treasury_a,
+        treasury_b,
+        TREASURER,
+        POOL,
+        LPT,
+        MINT_LPT,
+        treasury_s,
+        treasury_a,
+        treasury_b,
+        TREASURER,
+        src_s,
+        src_a,
+        src_b,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ],
+    ),
+    // Two legs, told apart by the pick
+    37 => {
+      let mut accounts = vec![
+        PAYER,
+        POOL,
+        VAULT,
+        treasury_s,
+        TREASURER,
+        SPLT_PROGRAM,
+        SYSVAR_CLOCK,
+      ];
+      for leg in [pick as usize, pick as usize / 9].iter() {
+        let (bid, ask) = (leg % 3, leg / 3 % 3);
+        accounts.extend(vec![SRC[bid], TREASURY[bid], SRC[ask], TREASURY[ask]]);
+      }
+      (4, accounts)
+    }
+    _ => (0, vec![]),
+  }
+}
+
+fn pack(tag: u8, args: &[u64]) -> Vec<u8> {
+  let mut data = vec![tag];
+  for arg in args.iter() {
+    data.extend_from_slice(&arg.to_le_bytes());
+  }
+  data
+}
+
+static STUBS: Once = Once::new();
+
+fuzz_target!(|input: Input| {
+  STUBS.call_once(|| {
+    set_syscall_stubs(Box::new(Stubs));
+  });
+  let mut model = Model::new();
+  if input.initialize {
+    let (_, accounts) = layout(0, 0);
+    model
+      .process(&pack(0, &[RESERVE, RESERVE, RESERVE]), &accounts)
+      .unwrap();
+  }
+  model.assert_covered();
+
+  for step in input.steps.iter() {
+    match step {
+      Step::Call {
+        tag,
+        args,
+        small,
+        pick,
+        stranger,
+        swaps,
+      } => {
+        let tag = tag % INSTRUCTIONS;
+        let (arity, mut accounts) = layout(tag, *pick);
+        // Keep most amounts within the funded balances
+        let mut args: Vec<u64> = args
+          .iter()
+          .take(arity)
+          .map(|&arg| if *small { arg % (2 * RESERVE) } else { arg })
+          .collect();
+        // Split ratios must add up to the whole vault
+        if tag == 19 && *small {
+          let ratio = DECIMALS / arity as u64;
+          args = vec![ratio; arity];
+          args[0] += DECIMALS - ratio * arity as u64;
+        }
+        if *stranger && accounts.first() == Some(&PAYER) {
+          accounts[0] = STRANGER;
+        }
+        if !accounts.is_empty() {
+          for &(i, j) in swaps.iter() {
+            let len = accounts.len();
+            accounts.swap(i as usize % len, j as usize % len);
+          }
+        }
+        let mut data = pack(tag, &args);
+        // Locks also take a schedule, cliff or linear, permissions and emergencies a flag
+        if tag == 26 || tag == 28 || tag == 34 {
+          data.push(pick % 2);
+        }
+        // Swaps flag their optional accounts
+        if tag == 3 {
+          data.push(pick / 9 % 8);
+        }
+        // Zaps take the token index first
+        if tag == 35 {
+          data.insert(1, pick % 3);
+        }
+        // Batches take the number of legs first
+        if tag == 37 {
+          data.insert(1, 2);
+        }
+        let _ = model.process(&data, &accounts);
+      }
+      Step::Raw { data, accounts } => {
+        let accounts: Vec<usize> = accounts.iter().map(|&i| i as usize % SIZE).collect();
+        let _ = model.process(data, &accounts);
+      }
+      Step::Warp { seconds } => model.warp(*seconds),
+    }
+    model.assert_covered();
+  }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use main::instruction::AppInstruction;
+
+fuzz_target!(|data: &[u8]| {
+  // Malformed data must be rejected, never panic
+  let _ = AppInstruction::unpack(data);
+});
@@ -35,6 +35,50 @@ pub enum AppError {
   InvalidMint,
   #[error("Exceed limit")]
   ExceedLimit,
+  #[error("Action still timelocked")]
+  Timelocked,
+  #[error("Invalid action")]
+  InvalidAction,
+  #[error("Reserve exceeds treasury balance")]
+  UncoveredReserve,
+  #[error("Pool not drained")]
+  UndrainedPool,
+  #[error("Insufficient liquidity")]
+  InsufficientLiquidity,
+  #[error("Invariant violated")]
+  InvariantViolated,
+  #[error("Invalid fee")]
+  InvalidFee,
+  #[error("Whitelist is full")]
+  FullWhitelist,
+  #[error("Invalid ratio")]
+  InvalidRatio,
+  #[error("Invalid beneficiary")]
+  InvalidBeneficiary,
+  #[error("Farm unmatched")]
+  UnmatchedFarm,
+  #[error("Invalid lock schedule")]
+  InvalidSchedule,
+  #[error("Liquidity still locked")]
+  LockedLiquidity,
+  #[error("Not a member of the pool")]
+  NotMember,
+  #[error("Exceed the reserve cap")]
+  ExceedReserveCap,
+  #[error("Exceed the trade cap")]
+  ExceedTradeCap,
+  #[error("Exceed the price impact limit")]
+  ExceedPriceImpact,
+  #[error("Exceed the outflow limit")]
+  ExceedOutflow,
+  #[error("Action target unmatched")]
+  UnmatchedTarget,
+  #[error("Cannot refer oneself")]
+  SelfReferral,
+  #[error("Vault shared by a split")]
+  SharedVault,
+  #[error("Action expired")]
+  ExpiredAction,
 }
 
 impl From<AppError> for ProgramError {
@@ -66,6 +110,28 @@ impl PrintProgramError for AppError {
       AppError::InsufficientFunds => msg!("Error: Insufficient funds"),
       AppError::InvalidMint => msg!("Error: Invalid mint"),
       AppError::ExceedLimit => msg!("Error: Exceed limit"),
+      AppError::Timelocked => msg!("Error: Action still timelocked"),
+      AppError::InvalidAction => msg!("Error: Invalid action"),
+      AppError::UncoveredReserve => msg!("Error: Reserve exceeds treasury balance"),
+      AppError::UndrainedPool => msg!("Error: Pool not drained"),
+      AppError::InsufficientLiquidity => msg!("Error: Insufficient liquidity"),
+      AppError::InvariantViolated => msg!("Error: Invariant violated"),
+      AppError::InvalidFee => msg!("Error: Invalid fee"),
+      AppError::FullWhitelist => msg!("Error: Whitelist is full"),
+      AppError::InvalidRatio => msg!("Error: Invalid ratio"),
+      AppError::InvalidBeneficiary => msg!("Error: Invalid beneficiary"),
+      AppError::UnmatchedFarm => msg!("Error: Farm unmatched"),
+      AppError::InvalidSchedule => msg!("Error: Invalid lock schedule"),
+      AppError::LockedLiquidity => msg!("Error: Liquidity still locked"),
+      AppError::NotMember => msg!("Error: Not a member of the pool"),
+      AppError::ExceedReserveCap => msg!("Error: Exceed the reserve cap"),
+      AppError::ExceedTradeCap => msg!("Error: Exceed the trade cap"),
+      AppError::ExceedPriceImpact => msg!("Error: Exceed the price impact limit"),
+      AppError::ExceedOutflow => msg!("Error: Exceed the outflow limit"),
+      AppError::UnmatchedTarget => msg!("Error: Action target unmatched"),
+      AppError::SelfReferral => msg!("Error: Cannot refer oneself"),
+      AppError::SharedVault => msg!("Error: Vault shared by a split"),
+      AppError::ExpiredAction => msg!("Error: Action expired"),
     }
   }
 }
@@ -0,0 +1,34 @@
+use solana_program::{msg, pubkey::Pubkey};
+
+///
+/// Events are written to the program logs for indexers
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum AppEvent {
+  Swap {
+    pool: Pubkey,
+    amount: u64,
+    paid_amount: u64,
+    earning: u64,
+    earning_in_sen: u64,
+    referrer: Option<Pubkey>,
+    referral: u64,
+  },
+  BatchSwap {
+    pool: Pubkey,
+    paid_amounts: Vec<u64>,
+  },
+  Donate {
+    pool: Pubkey,
+    donor: Pubkey,
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+  },
+}
+
+impl AppEvent {
+  pub fn emit(&self) {
+    msg!("Event: {:?}", self);
+  }
+}
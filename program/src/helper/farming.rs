@@ -0,0 +1,45 @@
+use crate::helper::math::{Decimal, Rounding};
+
+///
+/// Reward accrual of the LP farms
+/// Rewards are paid out of the farm, so every step rounds down (floor)
+///
+pub struct Farming {}
+
+impl Farming {
+  ///
+  /// The reward per staked LPT grows by rate * elapsed / total_stake
+  /// Nothing accrues while nobody stakes
+  ///
+  pub fn reward_per_share(
+    reward_per_share: Decimal,
+    reward_rate: u64,
+    elapsed: u64,
+    total_stake: u64,
+  ) -> Option<Decimal> {
+    if total_stake == 0 {
+      return Some(reward_per_share);
+    }
+    let increment = Decimal::from_u64(reward_rate)
+      .checked_mul(Decimal::from_u64(elapsed), Rounding::Floor)?
+      .checked_div(Decimal::from_u64(total_stake), Rounding::Floor)?;
+    reward_per_share.checked_add(increment)
+  }
+
+  ///
+  /// The reward already accounted for a stake
+  ///
+  pub fn debt(amount: u64, reward_per_share: Decimal) -> Option<Decimal> {
+    Decimal::from_u64(amount).checked_mul(reward_per_share, Rounding::Floor)
+  }
+
+  ///
+  /// The reward accrued by a stake since its debt
+  /// The fraction of a token is forfeited
+  ///
+  pub fn pending(amount: u64, reward_per_share: Decimal, debt: Decimal) -> Option<u64> {
+    Self::debt(amount, reward_per_share)?
+      .checked_sub(debt)?
+      .to_u64(Rounding::Floor)
+  }
+}
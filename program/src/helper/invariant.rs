@@ -0,0 +1,46 @@
+use crate::helper::math::U512;
+
+pub struct Invariant {}
+
+impl Invariant {
+  ///
+  /// The constant product of reserves
+  ///
+  pub fn product(reserve_s: u64, reserve_a: u64, reserve_b: u64) -> U512 {
+    U512::from(reserve_s) * U512::from(reserve_a) * U512::from(reserve_b)
+  }
+
+  ///
+  /// The product of reserves must not decrease (swap)
+  ///
+  pub fn is_product_kept(
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    new_reserve_s: u64,
+    new_reserve_a: u64,
+    new_reserve_b: u64,
+  ) -> bool {
+    Self::product(new_reserve_s, new_reserve_a, new_reserve_b)
+      >= Self::product(reserve_s, reserve_a, reserve_b)
+  }
+
+  ///
+  /// The product of reserves per cubic LPT must not decrease (add/remove liquidity)
+  /// new_k / new_lpt^3 >= k / lpt^3 <=> new_k * lpt^3 >= k * new_lpt^3
+  ///
+  pub fn is_share_kept(
+    (reserve_s, reserve_a, reserve_b): (u64, u64, u64),
+    reserve_lpt: u64,
+    (new_reserve_s, new_reserve_a, new_reserve_b): (u64, u64, u64),
+    new_reserve_lpt: u64,
+  ) -> bool {
+    if reserve_lpt == 0 || new_reserve_lpt == 0 {
+      return true;
+    }
+    let lpt = Self::product(reserve_lpt, reserve_lpt, reserve_lpt);
+    let new_lpt = Self::product(new_reserve_lpt, new_reserve_lpt, new_reserve_lpt);
+    Self::product(new_reserve_s, new_reserve_a, new_reserve_b) * lpt
+      >= Self::product(reserve_s, reserve_a, reserve_b) * new_lpt
+  }
+}
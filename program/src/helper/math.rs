@@ -1,3 +1,7 @@
+#![allow(clippy::assign_op_pattern, clippy::manual_range_contains)]
+
+use uint::construct_uint;
+
 ///
 /// Implement square/cubic root for u128
 ///
@@ -27,23 +31,193 @@ impl Roots for u128 {
   }
 
   ///
-  /// Newton's method (with a selectively initial guesses)
+  /// Newton's method (with an initial guess above the root)
+  /// The estimate decreases monotonically down to the floor of the root
   ///
   fn cbrt(self) -> Self {
     if self < 2 {
       return self;
     }
 
-    let bits = (128 - self.leading_zeros()) / 3;
+    let bits = (128 - self.leading_zeros()) / 3 + 1;
     let mut end: u128 = 1 << bits;
     loop {
       let next = (self / end.pow(2) + 2 * end) / 3;
-      if end != next {
-        end = next;
-      } else {
+      if next >= end {
         break;
       }
+      end = next;
     }
     end
   }
 }
+
+construct_uint! {
+  pub struct U256(4);
+}
+
+construct_uint! {
+  pub struct U512(8);
+}
+
+///
+/// Conversion between the wide integers
+///
+impl U512 {
+  pub fn from_u256(value: U256) -> Self {
+    let U256(ref words) = value;
+    U512([words[0], words[1], words[2], words[3], 0, 0, 0, 0])
+  }
+
+  pub fn to_u256(self) -> Option<U256> {
+    let U512(ref words) = self;
+    if words[4..].iter().any(|&word| word != 0) {
+      return None;
+    }
+    Some(U256([words[0], words[1], words[2], words[3]]))
+  }
+}
+
+///
+/// Implement square/cubic root for the wide integers
+/// Newton's method starting above the root, so that it converges to the floor
+///
+macro_rules! impl_roots {
+  ($type:ident) => {
+    impl Roots for $type {
+      fn sqrt(self) -> Self {
+        if self < $type::from(2) {
+          return self;
+        }
+        let bits = self.bits();
+        let mut end = $type::one() << (bits / 2 + 1);
+        loop {
+          let next = (end + self / end) >> 1;
+          if next >= end {
+            break;
+          }
+          end = next;
+        }
+        end
+      }
+
+      fn cbrt(self) -> Self {
+        if self < $type::from(2) {
+          return self;
+        }
+        let bits = self.bits();
+        let mut end = $type::one() << (bits / 3 + 1);
+        loop {
+          let next = (end * 2 + self / (end * end)) / 3;
+          if next >= end {
+            break;
+          }
+          end = next;
+        }
+        end
+      }
+    }
+  };
+}
+impl_roots!(U256);
+impl_roots!(U512);
+
+///
+/// Rounding direction
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+  Floor,
+  Ceil,
+}
+
+///
+/// Fixed-point decimal with 18 digits of precision, backed by U256
+/// Intermediate products are computed in U512 so that they never overflow
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(pub U256);
+
+impl Decimal {
+  pub const PRECISION: u64 = 1000000000000000000; // 10^18
+
+  pub fn zero() -> Self {
+    Decimal(U256::zero())
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.0.is_zero()
+  }
+
+  pub fn from_u64(value: u64) -> Self {
+    Decimal(U256::from(value) * U256::from(Self::PRECISION))
+  }
+
+  pub fn from_ratio(numerator: u64, denominator: u64, rounding: Rounding) -> Option<Self> {
+    Self::from_u64(numerator).checked_div(Self::from_u64(denominator), rounding)
+  }
+
+  pub fn to_u64(self, rounding: Rounding) -> Option<u64> {
+    let value = Self::div_round(
+      U512::from_u256(self.0),
+      U512::from(Self::PRECISION),
+      rounding,
+    )?;
+    if value > U512::from(u64::MAX) {
+      return None;
+    }
+    Some(value.low_u64())
+  }
+
+  pub fn checked_add(self, rhs: Self) -> Option<Self> {
+    self.0.checked_add(rhs.0).map(Decimal)
+  }
+
+  pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+    self.0.checked_sub(rhs.0).map(Decimal)
+  }
+
+  pub fn checked_mul(self, rhs: Self, rounding: Rounding) -> Option<Self> {
+    let product = U512::from_u256(self.0) * U512::from_u256(rhs.0);
+    let value = Self::div_round(product, U512::from(Self::PRECISION), rounding)?;
+    value.to_u256().map(Decimal)
+  }
+
+  pub fn checked_div(self, rhs: Self, rounding: Rounding) -> Option<Self> {
+    let numerator = U512::from_u256(self.0) * U512::from(Self::PRECISION);
+    let value = Self::div_round(numerator, U512::from_u256(rhs.0), rounding)?;
+    value.to_u256().map(Decimal)
+  }
+
+  pub fn sqrt(self, rounding: Rounding) -> Option<Self> {
+    // sqrt(x * 10^18) * 10^9 = sqrt(x) * 10^18
+    let radicand = U512::from_u256(self.0) * U512::from(Self::PRECISION);
+    let mut root = radicand.sqrt();
+    if rounding == Rounding::Ceil && root * root < radicand {
+      root = root + 1;
+    }
+    root.to_u256().map(Decimal)
+  }
+
+  pub fn cbrt(self, rounding: Rounding) -> Option<Self> {
+    // cbrt(x * 10^36) * 10^6 = cbrt(x) * 10^18
+    let radicand =
+      U512::from_u256(self.0) * U512::from(Self::PRECISION) * U512::from(Self::PRECISION);
+    let mut root = radicand.cbrt();
+    if rounding == Rounding::Ceil && root * root * root < radicand {
+      root = root + 1;
+    }
+    root.to_u256().map(Decimal)
+  }
+
+  fn div_round(numerator: U512, denominator: U512, rounding: Rounding) -> Option<U512> {
+    if denominator.is_zero() {
+      return None;
+    }
+    let (quotient, remainder) = numerator.div_mod(denominator);
+    if rounding == Rounding::Ceil && !remainder.is_zero() {
+      return quotient.checked_add(U512::one());
+    }
+    Some(quotient)
+  }
+}
@@ -1,3 +1,6 @@
+pub mod farming;
+pub mod invariant;
 pub mod math;
 pub mod oracle;
 pub mod pubutil;
+pub mod vesting;
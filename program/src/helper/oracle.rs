@@ -1,10 +1,16 @@
-use crate::helper::math::Roots;
+use crate::helper::math::{Decimal, Rounding};
 
-const TRIPPLE_PRECISION: u128 = 1000000000000000000; // 10^18
-const FEE: u64 = 2500000; // 0.25%
+pub const FEE: u64 = 2500000; // 0.25%
+pub const MAX_FEE: u64 = 100000000; // 10%
 const EARNING: u64 = 500000; // 0.05%
-const DECIMALS: u64 = 1000000000; // 10^9
+const VOLATILITY_WEIGHT: u64 = 100000000; // 10% of the latest move in the EMA
+pub const VOLATILITY_HALF_LIFE: i64 = 3600; // 1 hour without trades halves the volatility
+pub const DECIMALS: u64 = 1000000000; // 10^9
 
+///
+/// Rounding policy: amounts owed to the pool are rounded up (ceil),
+/// amounts paid out of the pool are rounded down (floor)
+///
 pub struct Oracle {}
 
 impl Oracle {
@@ -12,9 +18,11 @@ impl Oracle {
     if new_bid_reserve == 0 || bid_reserve == 0 || ask_reserve == 0 {
       return None;
     }
-    let new_ask_reserve = (bid_reserve as u128)
-      .checked_mul(ask_reserve as u128)?
-      .checked_div(new_bid_reserve as u128)? as u64;
+    // The remaining reserve is kept by the pool
+    let new_ask_reserve = Decimal::from_u64(bid_reserve)
+      .checked_mul(Decimal::from_u64(ask_reserve), Rounding::Ceil)?
+      .checked_div(Decimal::from_u64(new_bid_reserve), Rounding::Ceil)?
+      .to_u64(Rounding::Ceil)?;
     if new_ask_reserve == 0 {
       return None;
     }
@@ -26,19 +34,22 @@ impl Oracle {
     new_bid_reserve: u64,
     bid_reserve: u64,
     ask_reserve: u64,
+    fee: u64,
+    discount: u64,
     is_exempted: bool,
   ) -> Option<(u64, u64, u64)> {
     let new_ask_reserve_without_fee = Self::curve(new_bid_reserve, bid_reserve, ask_reserve)?;
     let paid_amount_without_fee = ask_reserve.checked_sub(new_ask_reserve_without_fee)?;
 
-    let fee = (paid_amount_without_fee as u128)
-      .checked_mul(FEE as u128)?
-      .checked_div(DECIMALS as u128)? as u64;
+    // Fee and earning are charged to the trader, less the whitelist discount
+    let fee = Decimal::from_u64(paid_amount_without_fee)
+      .checked_mul(Self::discounted(fee, discount)?, Rounding::Ceil)?
+      .to_u64(Rounding::Ceil)?;
     let mut earning: u64 = 0;
     if !is_exempted {
-      earning = (paid_amount_without_fee as u128)
-        .checked_mul(EARNING as u128)?
-        .checked_div(DECIMALS as u128)? as u64;
+      earning = Decimal::from_u64(paid_amount_without_fee)
+        .checked_mul(Self::discounted(EARNING, discount)?, Rounding::Ceil)?
+        .to_u64(Rounding::Ceil)?;
     }
 
     let paid_amount = paid_amount_without_fee
@@ -48,6 +59,98 @@ impl Oracle {
     Some((new_ask_reserve, paid_amount, earning))
   }
 
+  // Rate left to the trader after the discount, both in DECIMALS
+  fn discounted(rate: u64, discount: u64) -> Option<Decimal> {
+    Decimal::from_ratio(rate, DECIMALS, Rounding::Ceil)?.checked_mul(
+      Decimal::from_ratio(DECIMALS.checked_sub(discount)?, DECIMALS, Rounding::Ceil)?,
+      Rounding::Ceil,
+    )
+  }
+
+  // Fee in the owner bounds, rising with the volatility
+  pub fn fee(volatility: u64, fee_min: u64, fee_max: u64) -> u64 {
+    fee_min.saturating_add(volatility).min(fee_max)
+  }
+
+  // EMA of the absolute log-price moves of the traded pair
+  pub fn volatility(
+    volatility: u64,
+    bid_reserve: u64,
+    ask_reserve: u64,
+    new_bid_reserve: u64,
+    new_ask_reserve: u64,
+  ) -> Option<u64> {
+    let price =
+      Decimal::from_u64(bid_reserve).checked_div(Decimal::from_u64(ask_reserve), Rounding::Ceil)?;
+    let new_price = Decimal::from_u64(new_bid_reserve)
+      .checked_div(Decimal::from_u64(new_ask_reserve), Rounding::Ceil)?;
+    // |ln(p1 / p0)| is approximated by 2 * |p1 - p0| / (p1 + p0)
+    let diff = if new_price > price {
+      new_price.checked_sub(price)?
+    } else {
+      price.checked_sub(new_price)?
+    };
+    let weight = Decimal::from_ratio(VOLATILITY_WEIGHT, DECIMALS, Rounding::Ceil)?;
+    let decay = Decimal::from_ratio(DECIMALS - VOLATILITY_WEIGHT, DECIMALS, Rounding::Ceil)?;
+    // A higher volatility means a higher fee, so it rounds in favour of the pool
+    Decimal::from_u64(2)
+      .checked_mul(diff, Rounding::Ceil)?
+      .checked_div(new_price.checked_add(price)?, Rounding::Ceil)?
+      .checked_mul(Decimal::from_u64(DECIMALS), Rounding::Ceil)?
+      .checked_mul(weight, Rounding::Ceil)?
+      .checked_add(Decimal::from_u64(volatility).checked_mul(decay, Rounding::Ceil)?)?
+      .to_u64(Rounding::Ceil)
+  }
+
+  // Volatility left after the elapsed seconds without trades
+  // A higher volatility means a higher fee, so the decay rounds down
+  pub fn decay_volatility(volatility: u64, elapsed: i64) -> u64 {
+    if elapsed <= 0 {
+      return volatility;
+    }
+    let halvings = elapsed / VOLATILITY_HALF_LIFE;
+    if halvings >= 64 {
+      return 0;
+    }
+    let volatility = volatility >> halvings;
+    // 2^-x is approximated by 1 - x / 2 within a half-life
+    let rest = (elapsed % VOLATILITY_HALF_LIFE) as u128;
+    let decay = volatility as u128 * rest / (2 * VOLATILITY_HALF_LIFE as u128);
+    volatility - decay as u64
+  }
+
+  // Relative move of the pair price from the reference, |p1 / p0 - 1| in DECIMALS
+  // A larger impact trips the circuit breaker, so it rounds in favour of the pool,
+  // moves beyond u64 saturate
+  pub fn impact(
+    ref_bid_reserve: u64,
+    ref_ask_reserve: u64,
+    new_bid_reserve: u64,
+    new_ask_reserve: u64,
+  ) -> Option<u64> {
+    let price = Decimal::from_u64(new_bid_reserve)
+      .checked_mul(Decimal::from_u64(ref_ask_reserve), Rounding::Ceil)?;
+    let ref_price = Decimal::from_u64(ref_bid_reserve)
+      .checked_mul(Decimal::from_u64(new_ask_reserve), Rounding::Ceil)?;
+    let diff = if price > ref_price {
+      price.checked_sub(ref_price)?
+    } else {
+      ref_price.checked_sub(price)?
+    };
+    let impact = diff
+      .checked_mul(Decimal::from_u64(DECIMALS), Rounding::Ceil)?
+      .checked_div(ref_price, Rounding::Ceil)?;
+    Some(impact.to_u64(Rounding::Ceil).unwrap_or(u64::MAX))
+  }
+
+  // Part of an amount paid out at the given ratio, e.g. to a referrer or a beneficiary (floor)
+  pub fn portion(amount: u64, ratio: u64) -> Option<u64> {
+    if ratio > DECIMALS {
+      return None;
+    }
+    Self::share(amount, ratio, DECIMALS)
+  }
+
   pub fn _rake(
     delta: u64,
     reserve_s: u64,
@@ -60,33 +163,34 @@ impl Oracle {
     if delta == 0 {
       return Some((0, 0, 0));
     }
-    // Compute z
-    let cbrt_of_delta_plus_reserve = (delta as u128)
-      .checked_add(reserve_s as u128)?
-      .checked_mul(TRIPPLE_PRECISION)?
-      .cbrt(); // Single precision
-    let cbrt_of_reserve = (reserve_s as u128).checked_mul(TRIPPLE_PRECISION)?.cbrt(); // Single precision
-    let z = cbrt_of_delta_plus_reserve
-      .pow(2)
-      .checked_mul(cbrt_of_reserve)?
-      .checked_div(TRIPPLE_PRECISION)?
-      .checked_sub(reserve_s as u128)?;
-    // Compute x
+    let delta = Decimal::from_u64(delta);
+    let rs = Decimal::from_u64(reserve_s);
+    let delta_plus_rs = delta.checked_add(rs)?;
+    // Compute z = cbrt((delta + rs)^2 * rs) - rs
+    // z is not credited to the depositor, so it rounds in favour of the pool
+    let z = delta_plus_rs
+      .checked_mul(delta_plus_rs, Rounding::Ceil)?
+      .checked_mul(rs, Rounding::Ceil)?
+      .cbrt(Rounding::Ceil)?
+      .checked_sub(rs)?;
+    // Compute x = sqrt((z + rs) * rs) - rs
     let x = z
-      .checked_add(reserve_s as u128)?
-      .checked_mul(reserve_s as u128)?
-      .sqrt()
-      .checked_sub(reserve_s as u128)?;
+      .checked_add(rs)?
+      .checked_mul(rs, Rounding::Floor)?
+      .sqrt(Rounding::Floor)?
+      .checked_sub(rs)?;
     // Compute y
     let y = z.checked_sub(x)?;
     // Compute s, a, b
-    let s = (delta as u128).checked_sub(z)? as u64;
-    let a = (reserve_a as u128)
-      .checked_mul(x)?
-      .checked_div((reserve_s as u128).checked_add(x)?)? as u64;
-    let b = (reserve_b as u128)
-      .checked_mul(y)?
-      .checked_div((reserve_s as u128).checked_add(z)?)? as u64;
+    let s = delta.checked_sub(z)?.to_u64(Rounding::Floor)?;
+    let a = Decimal::from_u64(reserve_a)
+      .checked_mul(x, Rounding::Floor)?
+      .checked_div(rs.checked_add(x)?, Rounding::Floor)?
+      .to_u64(Rounding::Floor)?;
+    let b = Decimal::from_u64(reserve_b)
+      .checked_mul(y, Rounding::Floor)?
+      .checked_div(rs.checked_add(z)?, Rounding::Floor)?
+      .to_u64(Rounding::Floor)?;
     // Return
     Some((s, a, b))
   }
@@ -108,27 +212,45 @@ impl Oracle {
     let (s1, _a1, _b1) = Self::_rake(delta_s, rs, ra, rb)?;
     let rs = rs.checked_add(delta_s)?;
     let rs_prime = rs.checked_sub(s1)?;
-    let lpt1 = (s1 as u128)
-      .checked_mul(rlpt as u128)?
-      .checked_div(rs_prime as u128)? as u64;
+    let lpt1 = Self::share(s1, rlpt, rs_prime)?;
     let rlpt = rlpt.checked_add(lpt1)?;
 
     let (_a2, _b2, s2) = Self::_rake(delta_a, ra, rb, rs)?;
     let ra = ra.checked_add(delta_a)?;
     let rs_prime = rs.checked_sub(s2)?;
-    let lpt2 = (s2 as u128)
-      .checked_mul(rlpt as u128)?
-      .checked_div(rs_prime as u128)? as u64;
+    let lpt2 = Self::share(s2, rlpt, rs_prime)?;
     let rlpt = rlpt.checked_add(lpt2)?;
 
     let (_b3, s3, _a3) = Self::_rake(delta_b, rb, rs, ra)?;
     let rb = rb.checked_add(delta_b)?;
     let rs_prime = rs.checked_sub(s3)?;
-    let lpt3 = (s3 as u128)
-      .checked_mul(rlpt as u128)?
-      .checked_div(rs_prime as u128)? as u64;
+    let lpt3 = Self::share(s3, rlpt, rs_prime)?;
     let lpt = lpt1.checked_add(lpt2)?.checked_add(lpt3)?;
 
     Some((lpt, rs, ra, rb))
   }
+
+  pub fn unrake(
+    lpt: u64,
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    reserve_lpt: u64,
+  ) -> Option<(u64, u64, u64)> {
+    if reserve_lpt == 0 || lpt > reserve_lpt {
+      return None;
+    }
+    let delta_s = Self::share(lpt, reserve_s, reserve_lpt)?;
+    let delta_a = Self::share(lpt, reserve_a, reserve_lpt)?;
+    let delta_b = Self::share(lpt, reserve_b, reserve_lpt)?;
+    Some((delta_s, delta_a, delta_b))
+  }
+
+  // Paid-out amount corresponding to a part of a reserve: amount * reserve / total (floor)
+  fn share(amount: u64, reserve: u64, total: u64) -> Option<u64> {
+    Decimal::from_u64(amount)
+      .checked_mul(Decimal::from_u64(reserve), Rounding::Floor)?
+      .checked_div(Decimal::from_u64(total), Rounding::Floor)?
+      .to_u64(Rounding::Floor)
+  }
 }
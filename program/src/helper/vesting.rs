@@ -0,0 +1,38 @@
+use crate::helper::math::{Decimal, Rounding};
+
+///
+/// Release schedules of the liquidity locks
+/// Vested amounts are paid out, so they round down (floor)
+///
+pub struct Vesting {}
+
+impl Vesting {
+  ///
+  /// Everything vests at the unlock time
+  ///
+  pub fn cliff(amount: u64, unlock_ts: i64, now: i64) -> u64 {
+    if now >= unlock_ts {
+      amount
+    } else {
+      0
+    }
+  }
+
+  ///
+  /// Vests pro rata between the lock time and the unlock time
+  ///
+  pub fn linear(amount: u64, start_ts: i64, unlock_ts: i64, now: i64) -> Option<u64> {
+    if now >= unlock_ts {
+      return Some(amount);
+    }
+    if now <= start_ts {
+      return Some(0);
+    }
+    let elapsed = now.checked_sub(start_ts)? as u64;
+    let duration = unlock_ts.checked_sub(start_ts)? as u64;
+    Decimal::from_u64(amount)
+      .checked_mul(Decimal::from_u64(elapsed), Rounding::Floor)?
+      .checked_div(Decimal::from_u64(duration), Rounding::Floor)?
+      .to_u64(Rounding::Floor)
+  }
+}
@@ -1,13 +1,21 @@
 use crate::error::AppError;
+use crate::schema::{lock::LockSchedule, pool::RESERVE_PRECISION};
+use num_enum::TryFromPrimitive;
 use solana_program::program_error::ProgramError;
 use std::convert::TryInto;
 
+// Optional accounts of a swap, passed after the expected ones in this order
+pub const WITH_WHITELIST: u8 = 1;
+pub const WITH_MEMBER: u8 = 2;
+pub const WITH_REFERRER: u8 = 4;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum AppInstruction {
   InitializePool {
     reserve_s: u64,
     reserve_a: u64,
     reserve_b: u64,
+    reserve_precision: u8,
   },
   AddLiquidity {
     delta_s: u64,
@@ -20,6 +28,7 @@ pub enum AppInstruction {
   Swap {
     amount: u64,
     limit: u64,
+    flags: u8,
   },
   FreezePool,
   ThawPool,
@@ -27,6 +36,90 @@ pub enum AppInstruction {
     amount: u64,
   },
   TransferPoolOwnership,
+  ExecuteAction,
+  CancelAction,
+  Sync,
+  Skim,
+  Donate {
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+  },
+  ClosePool,
+  ReseedPool {
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+  },
+  UpdateFees {
+    fee_min: u64,
+    fee_max: u64,
+  },
+  InitializeWhitelist,
+  SetDiscount {
+    discount: u64,
+  },
+  UpdateReferral {
+    referral: u64,
+  },
+  SetSplit {
+    ratios: Vec<u64>,
+  },
+  DistributeEarnings,
+  InitializeFarm {
+    reward_rate: u64,
+  },
+  UpdateRewardRate {
+    reward_rate: u64,
+  },
+  Stake {
+    amount: u64,
+  },
+  Unstake {
+    amount: u64,
+  },
+  Harvest,
+  LockLiquidity {
+    lpt: u64,
+    unlock_ts: i64,
+    schedule: LockSchedule,
+  },
+  UnlockLiquidity,
+  UpdatePermission {
+    is_permissioned: bool,
+  },
+  AddMember,
+  RemoveMember,
+  UpdateCaps {
+    max_reserve_s: u64,
+    max_reserve_a: u64,
+    max_reserve_b: u64,
+    max_trade: u64,
+  },
+  UpdateCircuitBreaker {
+    max_impact: u64,
+  },
+  UpdateRateLimit {
+    max_outflow: u64,
+    outflow_window: u64,
+  },
+  UpdateEmergency {
+    is_emergency: bool,
+  },
+  ZapIn {
+    token_index: u8,
+    amount: u64,
+    min_lpt: u64,
+  },
+  MigrateLiquidity {
+    lpt: u64,
+    min_lpt_out: u64,
+  },
+  BatchSwap {
+    legs: Vec<(u64, u64)>,
+    flags: u8,
+  },
+  MigratePool,
 }
 impl AppInstruction {
   pub fn unpack(instruction: &[u8]) -> Result<Self, ProgramError> {
@@ -50,10 +143,13 @@ impl AppInstruction {
           .and_then(|slice| slice.try_into().ok())
           .map(u64::from_le_bytes)
           .ok_or(AppError::InvalidInstruction)?;
+        // The default minimum reserve if omitted
+        let reserve_precision = rest.get(24).copied().unwrap_or(RESERVE_PRECISION);
         Self::InitializePool {
           reserve_s,
           reserve_a,
           reserve_b,
+          reserve_precision,
         }
       }
       1 => {
@@ -97,7 +193,12 @@ impl AppInstruction {
           .and_then(|slice| slice.try_into().ok())
           .map(u64::from_le_bytes)
           .ok_or(AppError::InvalidInstruction)?;
-        Self::Swap { amount, limit }
+        let flags = Self::unpack_flags(rest.get(16))?;
+        Self::Swap {
+          amount,
+          limit,
+          flags,
+        }
       }
       4 => Self::FreezePool,
       5 => Self::ThawPool,
@@ -110,7 +211,288 @@ impl AppInstruction {
         Self::Earn { amount }
       }
       7 => Self::TransferPoolOwnership,
+      8 => Self::ExecuteAction,
+      9 => Self::CancelAction,
+      10 => Self::Sync,
+      11 => Self::Skim,
+      12 => {
+        let delta_s = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let delta_a = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let delta_b = rest
+          .get(16..24)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::Donate {
+          delta_s,
+          delta_a,
+          delta_b,
+        }
+      }
+      13 => Self::ClosePool,
+      14 => {
+        let reserve_s = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let reserve_a = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let reserve_b = rest
+          .get(16..24)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::ReseedPool {
+          reserve_s,
+          reserve_a,
+          reserve_b,
+        }
+      }
+      15 => {
+        let fee_min = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let fee_max = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateFees { fee_min, fee_max }
+      }
+      16 => Self::InitializeWhitelist,
+      17 => {
+        let discount = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::SetDiscount { discount }
+      }
+      18 => {
+        let referral = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateReferral { referral }
+      }
+      19 => {
+        if rest.len() % 8 != 0 {
+          return Err(AppError::InvalidInstruction.into());
+        }
+        let ratios = rest
+          .chunks(8)
+          .map(|slice| u64::from_le_bytes(slice.try_into().unwrap()))
+          .collect();
+        Self::SetSplit { ratios }
+      }
+      20 => Self::DistributeEarnings,
+      21 => {
+        let reward_rate = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::InitializeFarm { reward_rate }
+      }
+      22 => {
+        let reward_rate = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateRewardRate { reward_rate }
+      }
+      23 => {
+        let amount = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::Stake { amount }
+      }
+      24 => {
+        let amount = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::Unstake { amount }
+      }
+      25 => Self::Harvest,
+      26 => {
+        let lpt = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let unlock_ts = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(i64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let schedule = rest
+          .get(16)
+          .and_then(|&schedule| LockSchedule::try_from_primitive(schedule).ok())
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::LockLiquidity {
+          lpt,
+          unlock_ts,
+          schedule,
+        }
+      }
+      27 => Self::UnlockLiquidity,
+      28 => {
+        let is_permissioned = match rest.first() {
+          Some(0) => false,
+          Some(1) => true,
+          _ => return Err(AppError::InvalidInstruction.into()),
+        };
+        Self::UpdatePermission { is_permissioned }
+      }
+      29 => Self::AddMember,
+      30 => Self::RemoveMember,
+      31 => {
+        let max_reserve_s = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let max_reserve_a = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let max_reserve_b = rest
+          .get(16..24)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let max_trade = rest
+          .get(24..32)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateCaps {
+          max_reserve_s,
+          max_reserve_a,
+          max_reserve_b,
+          max_trade,
+        }
+      }
+      32 => {
+        let max_impact = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateCircuitBreaker { max_impact }
+      }
+      33 => {
+        let max_outflow = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let outflow_window = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::UpdateRateLimit {
+          max_outflow,
+          outflow_window,
+        }
+      }
+      34 => {
+        let is_emergency = match rest.first() {
+          Some(0) => false,
+          Some(1) => true,
+          _ => return Err(AppError::InvalidInstruction.into()),
+        };
+        Self::UpdateEmergency { is_emergency }
+      }
+      35 => {
+        // 0: S, 1: A, 2: B
+        let token_index = rest
+          .first()
+          .filter(|&&token_index| token_index < 3)
+          .copied()
+          .ok_or(AppError::InvalidInstruction)?;
+        let amount = rest
+          .get(1..9)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let min_lpt = rest
+          .get(9..17)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::ZapIn {
+          token_index,
+          amount,
+          min_lpt,
+        }
+      }
+      36 => {
+        let lpt = rest
+          .get(..8)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        let min_lpt_out = rest
+          .get(8..16)
+          .and_then(|slice| slice.try_into().ok())
+          .map(u64::from_le_bytes)
+          .ok_or(AppError::InvalidInstruction)?;
+        Self::MigrateLiquidity { lpt, min_lpt_out }
+      }
+      37 => {
+        // The number of legs, then the amount and the limit of each leg
+        let (&count, rest) = rest.split_first().ok_or(AppError::InvalidInstruction)?;
+        let mut legs = Vec::with_capacity(count as usize);
+        for i in 0..count as usize {
+          let amount = rest
+            .get(16 * i..16 * i + 8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(AppError::InvalidInstruction)?;
+          let limit = rest
+            .get(16 * i + 8..16 * i + 16)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(AppError::InvalidInstruction)?;
+          legs.push((amount, limit));
+        }
+        let flags = Self::unpack_flags(rest.get(16 * count as usize))?;
+        Self::BatchSwap { legs, flags }
+      }
+      38 => Self::MigratePool,
       _ => return Err(AppError::InvalidInstruction.into()),
     })
   }
+
+  // Flags of the optional accounts, none if omitted
+  fn unpack_flags(flags: Option<&u8>) -> Result<u8, ProgramError> {
+    let flags = flags.copied().unwrap_or(0);
+    if flags & !(WITH_WHITELIST | WITH_MEMBER | WITH_REFERRER) != 0 {
+      return Err(AppError::InvalidInstruction.into());
+    }
+    Ok(flags)
+  }
 }
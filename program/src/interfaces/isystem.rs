@@ -0,0 +1,111 @@
+use solana_program::{
+  instruction::{AccountMeta, Instruction},
+  program_error::ProgramError,
+  pubkey::Pubkey,
+};
+use std::mem::size_of;
+
+pub struct ISYSTEM {}
+
+impl ISYSTEM {
+  ///
+  /// Create account
+  ///
+  pub fn create_account(
+    lamports: u64,
+    space: u64,
+    owner: Pubkey,
+    funding_acc: Pubkey,
+    target_acc: Pubkey,
+    program_id: Pubkey,
+  ) -> Result<Instruction, ProgramError> {
+    // Build data
+    let mut data = Vec::with_capacity(size_of::<Self>());
+    // CreateAccount - Code 0
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data.extend_from_slice(&space.to_le_bytes());
+    data.extend_from_slice(&owner.to_bytes());
+    // Build accounts
+    let accounts = vec![
+      AccountMeta::new(funding_acc, true),
+      AccountMeta::new(target_acc, true),
+    ];
+    // Return
+    Ok(Instruction {
+      program_id,
+      accounts,
+      data,
+    })
+  }
+  ///
+  /// Assign
+  ///
+  pub fn assign(
+    owner: Pubkey,
+    target_acc: Pubkey,
+    program_id: Pubkey,
+  ) -> Result<Instruction, ProgramError> {
+    // Build data
+    let mut data = Vec::with_capacity(size_of::<Self>());
+    // Assign - Code 1
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&owner.to_bytes());
+    // Build accounts
+    let accounts = vec![AccountMeta::new(target_acc, true)];
+    // Return
+    Ok(Instruction {
+      program_id,
+      accounts,
+      data,
+    })
+  }
+  ///
+  /// Transfer
+  ///
+  pub fn transfer(
+    lamports: u64,
+    src_acc: Pubkey,
+    dst_acc: Pubkey,
+    program_id: Pubkey,
+  ) -> Result<Instruction, ProgramError> {
+    // Build data
+    let mut data = Vec::with_capacity(size_of::<Self>());
+    // Transfer - Code 2
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&lamports.to_le_bytes());
+    // Build accounts
+    let accounts = vec![
+      AccountMeta::new(src_acc, true),
+      AccountMeta::new(dst_acc, false),
+    ];
+    // Return
+    Ok(Instruction {
+      program_id,
+      accounts,
+      data,
+    })
+  }
+  ///
+  /// Allocate
+  ///
+  pub fn allocate(
+    space: u64,
+    target_acc: Pubkey,
+    program_id: Pubkey,
+  ) -> Result<Instruction, ProgramError> {
+    // Build data
+    let mut data = Vec::with_capacity(size_of::<Self>());
+    // Allocate - Code 8
+    data.extend_from_slice(&8u32.to_le_bytes());
+    data.extend_from_slice(&space.to_le_bytes());
+    // Build accounts
+    let accounts = vec![AccountMeta::new(target_acc, true)];
+    // Return
+    Ok(Instruction {
+      program_id,
+      accounts,
+      data,
+    })
+  }
+}
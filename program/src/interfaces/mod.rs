@@ -1,4 +1,6 @@
 pub mod isplata;
 pub mod isplt;
+pub mod isystem;
 pub mod xsplata;
 pub mod xsplt;
+pub mod xsystem;
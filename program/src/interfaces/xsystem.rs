@@ -0,0 +1,86 @@
+use crate::interfaces::isystem::ISYSTEM;
+use solana_program::{
+  account_info::AccountInfo, entrypoint::ProgramResult, program::invoke_signed, pubkey::Pubkey,
+};
+
+pub struct XSYSTEM {}
+
+impl XSYSTEM {
+  ///
+  /// Create a program account at an address derived from the seed,
+  /// even if someone already sent lamports to it
+  ///
+  pub fn create_account<'a>(
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+    funding_acc: &AccountInfo<'a>,
+    target_acc: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    seed: &[&[&[u8]]],
+  ) -> ProgramResult {
+    if target_acc.lamports() == 0 {
+      let ix = ISYSTEM::create_account(
+        lamports,
+        space,
+        *owner,
+        *funding_acc.key,
+        *target_acc.key,
+        *system_program.key,
+      )?;
+      invoke_signed(
+        &ix,
+        &[
+          funding_acc.clone(),
+          target_acc.clone(),
+          system_program.clone(),
+        ],
+        seed,
+      )?;
+      return Ok(());
+    }
+    // Top up the rent, then allocate and assign
+    let shortfall = lamports.saturating_sub(target_acc.lamports());
+    if shortfall > 0 {
+      let ix = ISYSTEM::transfer(
+        shortfall,
+        *funding_acc.key,
+        *target_acc.key,
+        *system_program.key,
+      )?;
+      invoke_signed(
+        &ix,
+        &[
+          funding_acc.clone(),
+          target_acc.clone(),
+          system_program.clone(),
+        ],
+        seed,
+      )?;
+    }
+    let ix = ISYSTEM::allocate(space, *target_acc.key, *system_program.key)?;
+    invoke_signed(&ix, &[target_acc.clone(), system_program.clone()], seed)?;
+    let ix = ISYSTEM::assign(*owner, *target_acc.key, *system_program.key)?;
+    invoke_signed(&ix, &[target_acc.clone(), system_program.clone()], seed)?;
+    Ok(())
+  }
+
+  ///
+  /// Transfer lamports from a system account
+  ///
+  pub fn transfer<'a>(
+    lamports: u64,
+    src_acc: &AccountInfo<'a>,
+    dst_acc: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    seed: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let ix = ISYSTEM::transfer(lamports, *src_acc.key, *dst_acc.key, *system_program.key)?;
+    invoke_signed(
+      &ix,
+      &[src_acc.clone(), dst_acc.clone(), system_program.clone()],
+      seed,
+    )?;
+    Ok(())
+  }
+}
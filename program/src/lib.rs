@@ -2,6 +2,7 @@
 
 pub mod entrypoint;
 pub mod error;
+pub mod event;
 pub mod helper;
 pub mod instruction;
 pub mod interfaces;
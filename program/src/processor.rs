@@ -1,19 +1,47 @@
 use crate::error::AppError;
-use crate::helper::{oracle::Oracle, pubutil::Boolean};
-use crate::instruction::AppInstruction;
-use crate::interfaces::{xsplata::XSPLATA, xsplt::XSPLT};
+use crate::event::AppEvent;
+use crate::helper::{
+  farming::Farming,
+  invariant::Invariant,
+  math::Decimal,
+  oracle::{Oracle, DECIMALS, FEE, MAX_FEE},
+  pubutil::Boolean,
+  vesting::Vesting,
+};
+use crate::instruction::{AppInstruction, WITH_MEMBER, WITH_REFERRER, WITH_WHITELIST};
+use crate::interfaces::{xsplata::XSPLATA, xsplt::XSPLT, xsystem::XSYSTEM};
 use crate::schema::{
+  account::Account,
+  farm::Farm,
+  lock::{Lock, LockSchedule},
+  member::Member,
   mint::Mint,
-  pool::{Pool, PoolState},
+  pool::{minimum_reserve, Pool, PoolState, MINIMUM_LIQUIDITY, POOL_VERSION, RESERVE_PRECISION},
+  split::{Share, Split, MAX_BENEFICIARIES},
+  staker::Staker,
+  timelock::{Action, Timelock, TimelockState, TIMELOCK_DELAY},
+  whitelist::Whitelist,
 };
 use solana_program::{
   account_info::{next_account_info, AccountInfo},
+  clock::Clock,
   entrypoint::ProgramResult,
   msg,
+  program::set_return_data,
+  program_error::ProgramError,
   program_pack::{IsInitialized, Pack},
   pubkey::{Pubkey, PubkeyError},
+  rent::Rent,
+  sysvar::Sysvar,
 };
 
+// Whitelist, allowlist entry and referrer of a swap
+type OptionalAccounts<'a, 'b> = (
+  Option<&'a AccountInfo<'b>>,
+  Option<&'a AccountInfo<'b>>,
+  Option<&'a AccountInfo<'b>>,
+);
+
 pub struct Processor {}
 
 impl Processor {
@@ -31,9 +59,17 @@ impl Processor {
         reserve_s,
         reserve_a,
         reserve_b,
+        reserve_precision,
       } => {
         msg!("Calling InitializePool function");
-        Self::initialize_pool(reserve_s, reserve_a, reserve_b, program_id, accounts)
+        Self::initialize_pool(
+          reserve_s,
+          reserve_a,
+          reserve_b,
+          reserve_precision,
+          program_id,
+          accounts,
+        )
       }
 
       AppInstruction::AddLiquidity {
@@ -50,9 +86,13 @@ impl Processor {
         Self::remove_liquidity(lpt, program_id, accounts)
       }
 
-      AppInstruction::Swap { amount, limit } => {
+      AppInstruction::Swap {
+        amount,
+        limit,
+        flags,
+      } => {
         msg!("Calling Swap function");
-        Self::swap(amount, limit, program_id, accounts)
+        Self::swap(amount, limit, flags, program_id, accounts)
       }
 
       AppInstruction::FreezePool {} => {
@@ -74,6 +114,191 @@ impl Processor {
         msg!("Calling TransferPoolOwnership function");
         Self::transfer_pool_ownership(program_id, accounts)
       }
+
+      AppInstruction::ExecuteAction => {
+        msg!("Calling ExecuteAction function");
+        Self::execute_action(program_id, accounts)
+      }
+
+      AppInstruction::CancelAction => {
+        msg!("Calling CancelAction function");
+        Self::cancel_action(program_id, accounts)
+      }
+
+      AppInstruction::Sync => {
+        msg!("Calling Sync function");
+        Self::sync(program_id, accounts)
+      }
+
+      AppInstruction::Skim => {
+        msg!("Calling Skim function");
+        Self::skim(program_id, accounts)
+      }
+
+      AppInstruction::Donate {
+        delta_s,
+        delta_a,
+        delta_b,
+      } => {
+        msg!("Calling Donate function");
+        Self::donate(delta_s, delta_a, delta_b, program_id, accounts)
+      }
+
+      AppInstruction::ClosePool => {
+        msg!("Calling ClosePool function");
+        Self::close_pool(program_id, accounts)
+      }
+
+      AppInstruction::ReseedPool {
+        reserve_s,
+        reserve_a,
+        reserve_b,
+      } => {
+        msg!("Calling ReseedPool function");
+        Self::reseed_pool(reserve_s, reserve_a, reserve_b, program_id, accounts)
+      }
+
+      AppInstruction::UpdateFees { fee_min, fee_max } => {
+        msg!("Calling UpdateFees function");
+        Self::update_fees(fee_min, fee_max, program_id, accounts)
+      }
+
+      AppInstruction::InitializeWhitelist => {
+        msg!("Calling InitializeWhitelist function");
+        Self::initialize_whitelist(program_id, accounts)
+      }
+
+      AppInstruction::SetDiscount { discount } => {
+        msg!("Calling SetDiscount function");
+        Self::set_discount(discount, program_id, accounts)
+      }
+
+      AppInstruction::UpdateReferral { referral } => {
+        msg!("Calling UpdateReferral function");
+        Self::update_referral(referral, program_id, accounts)
+      }
+
+      AppInstruction::SetSplit { ratios } => {
+        msg!("Calling SetSplit function");
+        Self::set_split(&ratios, program_id, accounts)
+      }
+
+      AppInstruction::DistributeEarnings => {
+        msg!("Calling DistributeEarnings function");
+        Self::distribute_earnings(program_id, accounts)
+      }
+
+      AppInstruction::InitializeFarm { reward_rate } => {
+        msg!("Calling InitializeFarm function");
+        Self::initialize_farm(reward_rate, program_id, accounts)
+      }
+
+      AppInstruction::UpdateRewardRate { reward_rate } => {
+        msg!("Calling UpdateRewardRate function");
+        Self::update_reward_rate(reward_rate, program_id, accounts)
+      }
+
+      AppInstruction::Stake { amount } => {
+        msg!("Calling Stake function");
+        Self::stake(amount, program_id, accounts)
+      }
+
+      AppInstruction::Unstake { amount } => {
+        msg!("Calling Unstake function");
+        Self::unstake(amount, program_id, accounts)
+      }
+
+      AppInstruction::Harvest => {
+        msg!("Calling Harvest function");
+        Self::harvest(program_id, accounts)
+      }
+
+      AppInstruction::LockLiquidity {
+        lpt,
+        unlock_ts,
+        schedule,
+      } => {
+        msg!("Calling LockLiquidity function");
+        Self::lock_liquidity(lpt, unlock_ts, schedule, program_id, accounts)
+      }
+
+      AppInstruction::UnlockLiquidity => {
+        msg!("Calling UnlockLiquidity function");
+        Self::unlock_liquidity(program_id, accounts)
+      }
+
+      AppInstruction::UpdatePermission { is_permissioned } => {
+        msg!("Calling UpdatePermission function");
+        Self::update_permission(is_permissioned, program_id, accounts)
+      }
+
+      AppInstruction::AddMember => {
+        msg!("Calling AddMember function");
+        Self::add_member(program_id, accounts)
+      }
+
+      AppInstruction::RemoveMember => {
+        msg!("Calling RemoveMember function");
+        Self::remove_member(program_id, accounts)
+      }
+
+      AppInstruction::UpdateCaps {
+        max_reserve_s,
+        max_reserve_a,
+        max_reserve_b,
+        max_trade,
+      } => {
+        msg!("Calling UpdateCaps function");
+        Self::update_caps(
+          max_reserve_s,
+          max_reserve_a,
+          max_reserve_b,
+          max_trade,
+          program_id,
+          accounts,
+        )
+      }
+
+      AppInstruction::UpdateCircuitBreaker { max_impact } => {
+        msg!("Calling UpdateCircuitBreaker function");
+        Self::update_circuit_breaker(max_impact, program_id, accounts)
+      }
+
+      AppInstruction::UpdateRateLimit {
+        max_outflow,
+        outflow_window,
+      } => {
+        msg!("Calling UpdateRateLimit function");
+        Self::update_rate_limit(max_outflow, outflow_window, program_id, accounts)
+      }
+
+      AppInstruction::UpdateEmergency { is_emergency } => {
+        msg!("Calling UpdateEmergency function");
+        Self::update_emergency(is_emergency, program_id, accounts)
+      }
+
+      AppInstruction::ZapIn {
+        token_index,
+        amount,
+        min_lpt,
+      } => {
+        msg!("Calling ZapIn function");
+        Self::zap_in(token_index, amount, min_lpt, program_id, accounts)
+      }
+
+      AppInstruction::MigrateLiquidity { lpt, min_lpt_out } => {
+        msg!("Calling MigrateLiquidity function");
+        Self::migrate_liquidity(lpt, min_lpt_out, program_id, accounts)
+      }
+
+      AppInstruction::BatchSwap { legs, flags } => {
+        msg!("Calling BatchSwap function");
+        Self::batch_swap(&legs, flags, program_id, accounts)
+      }
+      AppInstruction::MigratePool => {
+        msg!("Calling MigratePool function");
+        Self::migrate_pool(program_id, accounts)
+      }
     }
   }
 
@@ -85,6 +310,7 @@ impl Processor {
     reserve_s: u64,
     reserve_a: u64,
     reserve_b: u64,
+    reserve_precision: u8,
     program_id: &Pubkey,
     accounts: &[AccountInfo],
   ) -> ProgramResult {
@@ -93,6 +319,7 @@ impl Processor {
     let owner = next_account_info(accounts_iter)?;
     let pool_acc = next_account_info(accounts_iter)?;
     let lpt_acc = next_account_info(accounts_iter)?;
+    let burn_acc = next_account_info(accounts_iter)?;
     let mint_lpt_acc = next_account_info(accounts_iter)?;
     let vault_acc = next_account_info(accounts_iter)?;
     let proof_acc = next_account_info(accounts_iter)?; // program_id xor treasurer xor pool_id
@@ -133,6 +360,15 @@ impl Processor {
     if reserve_s == 0 || reserve_a == 0 || reserve_b == 0 {
       return Err(AppError::ZeroValue.into());
     }
+    Self::is_minimum_reserve(
+      &[reserve_s, reserve_a, reserve_b],
+      &[mint_s_acc, mint_a_acc, mint_b_acc],
+      reserve_precision,
+    )?;
+    // The creator must be left with some LPT once the minimum liquidity is locked
+    if reserve_s <= MINIMUM_LIQUIDITY {
+      return Err(AppError::InsufficientLiquidity.into());
+    }
 
     // Initialize treasury S
     XSPLATA::initialize_account(
@@ -225,13 +461,36 @@ impl Processor {
     )?;
     // Mint LPT
     XSPLT::mint_to(
-      reserve_s,
+      reserve_s
+        .checked_sub(MINIMUM_LIQUIDITY)
+        .ok_or(AppError::Overflow)?,
       mint_lpt_acc,
       lpt_acc,
       treasurer,
       splt_program,
       seed,
     )?;
+    // Initialize burn account
+    XSPLATA::initialize_account(
+      payer,
+      burn_acc,
+      treasurer,
+      mint_lpt_acc,
+      system_program,
+      splt_program,
+      sysvar_rent_acc,
+      splata_program,
+      &[],
+    )?;
+    // Lock the minimum liquidity forever
+    XSPLT::mint_to(
+      MINIMUM_LIQUIDITY,
+      mint_lpt_acc,
+      burn_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
 
     // Initialize vault
     XSPLT::initialize_account(
@@ -257,7 +516,39 @@ impl Processor {
     pool_data.mint_b = *mint_b_acc.key;
     pool_data.treasury_b = *treasury_b_acc.key;
     pool_data.reserve_b = reserve_b;
+    pool_data.version = POOL_VERSION;
+    pool_data.fee_min = FEE;
+    pool_data.fee_max = FEE;
+    pool_data.volatility = 0;
+    pool_data.referral = 0;
+    pool_data.is_permissioned = false;
+    pool_data.max_reserve_s = 0;
+    pool_data.max_reserve_a = 0;
+    pool_data.max_reserve_b = 0;
+    pool_data.max_trade = 0;
+    pool_data.max_impact = 0;
+    pool_data.ref_slot = 0;
+    pool_data.ref_reserve_s = 0;
+    pool_data.ref_reserve_a = 0;
+    pool_data.ref_reserve_b = 0;
+    pool_data.max_outflow = 0;
+    pool_data.outflow_window = 0;
+    pool_data.window_start = 0;
+    pool_data.window_reserve_s = 0;
+    pool_data.window_reserve_a = 0;
+    pool_data.window_reserve_b = 0;
+    pool_data.outflow_s = 0;
+    pool_data.outflow_a = 0;
+    pool_data.outflow_b = 0;
+    pool_data.is_emergency = false;
+    pool_data.burn = *burn_acc.key;
+    pool_data.reserve_precision = reserve_precision;
+    pool_data.volatility_ts = 0;
     Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
 
     Ok(())
   }
@@ -286,13 +577,18 @@ impl Processor {
 
     let treasurer = next_account_info(accounts_iter)?;
     let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    // Only required by permissioned pools
+    let member_acc = accounts_iter.next();
 
     Self::is_program(program_id, &[pool_acc])?;
     Self::is_signer(&[owner])?;
 
     let mint_lpt_data = Mint::unpack(&mint_lpt_acc.data.borrow())?;
     let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
     let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    Self::is_member(&pool_data, pool_acc, owner, member_acc, program_id)?;
     if pool_data.mint_lpt != *mint_lpt_acc.key
       || pool_data.treasury_s != *treasury_s_acc.key
       || pool_data.treasury_a != *treasury_a_acc.key
@@ -304,6 +600,8 @@ impl Processor {
       return Err(AppError::ZeroValue.into());
     }
 
+    Self::record_reference(&mut pool_data, &clock);
+    let prev_pool_data = pool_data;
     let (lpt, reserve_s, reserve_a, reserve_b) = Oracle::rake(
       delta_s,
       delta_a,
@@ -314,6 +612,18 @@ impl Processor {
       mint_lpt_data.supply,
     )
     .ok_or(AppError::Overflow)?;
+    // Deposits may not push a reserve beyond its cap, zero is uncapped
+    for &(delta, reserve, max_reserve) in [
+      (delta_s, reserve_s, pool_data.max_reserve_s),
+      (delta_a, reserve_a, pool_data.max_reserve_a),
+      (delta_b, reserve_b, pool_data.max_reserve_b),
+    ]
+    .iter()
+    {
+      if delta > 0 && max_reserve != 0 && reserve > max_reserve {
+        return Err(AppError::ExceedReserveCap.into());
+      }
+    }
 
     // Deposit token
     if delta_s > 0 {
@@ -328,10 +638,120 @@ impl Processor {
       XSPLT::transfer(delta_b, src_b_acc, treasury_b_acc, owner, splt_program, &[])?;
       pool_data.reserve_b = reserve_b;
     }
+    Self::is_share_kept(
+      &prev_pool_data,
+      mint_lpt_data.supply,
+      &pool_data,
+      mint_lpt_data
+        .supply
+        .checked_add(lpt)
+        .ok_or(AppError::Overflow)?,
+    )?;
+    // Update pool
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    // Mint LPT
+    XSPLT::mint_to(lpt, mint_lpt_acc, lpt_acc, treasurer, splt_program, seed)?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
+
+    Ok(())
+  }
+
+  pub fn zap_in(
+    token_index: u8,
+    amount: u64,
+    min_lpt: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let lpt_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+
+    let src_acc = next_account_info(accounts_iter)?;
+    let treasury_acc = next_account_info(accounts_iter)?;
+
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    // Only required by permissioned pools
+    let member_acc = accounts_iter.next();
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+
+    let mint_lpt_data = Mint::unpack(&mint_lpt_acc.data.borrow())?;
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    Self::is_member(&pool_data, pool_acc, owner, member_acc, program_id)?;
+    if pool_data.mint_lpt != *mint_lpt_acc.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    let (code, _) = pool_data
+      .get_reserve(treasury_acc.key)
+      .ok_or(AppError::UnmatchedPool)?;
+    if code != token_index {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if amount == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    Self::record_reference(&mut pool_data, &clock);
+    let prev_pool_data = pool_data;
+    let mut deltas = [0; 3];
+    deltas[code as usize] = amount;
+    let [delta_s, delta_a, delta_b] = deltas;
+    let (lpt, reserve_s, reserve_a, reserve_b) = Oracle::rake(
+      delta_s,
+      delta_a,
+      delta_b,
+      pool_data.reserve_s,
+      pool_data.reserve_a,
+      pool_data.reserve_b,
+      mint_lpt_data.supply,
+    )
+    .ok_or(AppError::Overflow)?;
+    if lpt < min_lpt {
+      return Err(AppError::ExceedLimit.into());
+    }
+    let (reserve, max_reserve) = match code {
+      0 => (reserve_s, pool_data.max_reserve_s),
+      1 => (reserve_a, pool_data.max_reserve_a),
+      2 => (reserve_b, pool_data.max_reserve_b),
+      _ => return Err(AppError::UnmatchedPool.into()),
+    };
+    if max_reserve != 0 && reserve > max_reserve {
+      return Err(AppError::ExceedReserveCap.into());
+    }
+
+    // Deposit token
+    XSPLT::transfer(amount, src_acc, treasury_acc, owner, splt_program, &[])?;
+    match code {
+      0 => pool_data.reserve_s = reserve_s,
+      1 => pool_data.reserve_a = reserve_a,
+      2 => pool_data.reserve_b = reserve_b,
+      _ => return Err(AppError::UnmatchedPool.into()),
+    }
+    Self::is_share_kept(
+      &prev_pool_data,
+      mint_lpt_data.supply,
+      &pool_data,
+      mint_lpt_data
+        .supply
+        .checked_add(lpt)
+        .ok_or(AppError::Overflow)?,
+    )?;
     // Update pool
     Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
     // Mint LPT
     XSPLT::mint_to(lpt, mint_lpt_acc, lpt_acc, treasurer, splt_program, seed)?;
+    Self::is_covered(&pool_data, &[treasury_acc])?;
 
     Ok(())
   }
@@ -358,6 +778,7 @@ impl Processor {
 
     let treasurer = next_account_info(accounts_iter)?;
     let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
 
     Self::is_program(program_id, &[pool_acc])?;
     Self::is_signer(&[owner])?;
@@ -365,6 +786,7 @@ impl Processor {
 
     let mint_lpt_data = Mint::unpack(&mint_lpt_acc.data.borrow())?;
     let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
     if pool_data.mint_lpt != *mint_lpt_acc.key
       || pool_data.treasury_s != *treasury_s_acc.key
       || pool_data.treasury_a != *treasury_a_acc.key
@@ -380,21 +802,24 @@ impl Processor {
     }
 
     // Compute corresponding paid-back reserve
-    let delta_s = (lpt as u128)
-      .checked_mul(pool_data.reserve_s as u128)
-      .ok_or(AppError::Overflow)?
-      .checked_div(mint_lpt_data.supply as u128)
-      .ok_or(AppError::Overflow)? as u64;
-    let delta_a = (lpt as u128)
-      .checked_mul(pool_data.reserve_a as u128)
-      .ok_or(AppError::Overflow)?
-      .checked_div(mint_lpt_data.supply as u128)
-      .ok_or(AppError::Overflow)? as u64;
-    let delta_b = (lpt as u128)
-      .checked_mul(pool_data.reserve_b as u128)
-      .ok_or(AppError::Overflow)?
-      .checked_div(mint_lpt_data.supply as u128)
-      .ok_or(AppError::Overflow)? as u64;
+    Self::record_reference(&mut pool_data, &clock);
+    let prev_pool_data = pool_data;
+    let (delta_s, delta_a, delta_b) = Oracle::unrake(
+      lpt,
+      pool_data.reserve_s,
+      pool_data.reserve_a,
+      pool_data.reserve_b,
+      mint_lpt_data.supply,
+    )
+    .ok_or(AppError::Overflow)?;
+    // The emergency exit is not rate limited
+    if !pool_data.is_emergency {
+      Self::track_outflow(
+        &mut pool_data,
+        [delta_s, delta_a, delta_b],
+        clock.unix_timestamp,
+      )?;
+    }
     // Burn LPT
     XSPLT::burn(lpt, lpt_acc, mint_lpt_acc, owner, splt_program, seed)?;
     // Update pool data
@@ -413,6 +838,15 @@ impl Processor {
     if pool_data.reserve_s == 0 {
       pool_data.state = PoolState::Frozen;
     }
+    Self::is_share_kept(
+      &prev_pool_data,
+      mint_lpt_data.supply,
+      &pool_data,
+      mint_lpt_data
+        .supply
+        .checked_sub(lpt)
+        .ok_or(AppError::Overflow)?,
+    )?;
     Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
     // Withdraw token
     XSPLT::transfer(
@@ -439,6 +873,224 @@ impl Processor {
       splt_program,
       seed,
     )?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
+
+    Ok(())
+  }
+
+  pub fn migrate_liquidity(
+    lpt: u64,
+    min_lpt_out: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+
+    let src_pool_acc = next_account_info(accounts_iter)?;
+    let src_lpt_acc = next_account_info(accounts_iter)?;
+    let src_mint_lpt_acc = next_account_info(accounts_iter)?;
+    let src_treasury_s_acc = next_account_info(accounts_iter)?;
+    let src_treasury_a_acc = next_account_info(accounts_iter)?;
+    let src_treasury_b_acc = next_account_info(accounts_iter)?;
+    let src_treasurer = next_account_info(accounts_iter)?;
+
+    let dst_pool_acc = next_account_info(accounts_iter)?;
+    let dst_lpt_acc = next_account_info(accounts_iter)?;
+    let dst_mint_lpt_acc = next_account_info(accounts_iter)?;
+    let dst_treasury_s_acc = next_account_info(accounts_iter)?;
+    let dst_treasury_a_acc = next_account_info(accounts_iter)?;
+    let dst_treasury_b_acc = next_account_info(accounts_iter)?;
+    let dst_treasurer = next_account_info(accounts_iter)?;
+
+    // Receive the tokens the destination pool does not hold
+    let refund_s_acc = next_account_info(accounts_iter)?;
+    let refund_a_acc = next_account_info(accounts_iter)?;
+    let refund_b_acc = next_account_info(accounts_iter)?;
+
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    // Only required by permissioned destination pools
+    let member_acc = accounts_iter.next();
+
+    Self::is_program(program_id, &[src_pool_acc, dst_pool_acc])?;
+    Self::is_signer(&[owner])?;
+    if src_pool_acc.key == dst_pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    let src_seed: &[&[&[u8]]] =
+      &[&[&Self::safe_seed(src_pool_acc, src_treasurer, program_id)?[..]]];
+    let dst_seed: &[&[&[u8]]] =
+      &[&[&Self::safe_seed(dst_pool_acc, dst_treasurer, program_id)?[..]]];
+
+    let src_mint_lpt_data = Mint::unpack(&src_mint_lpt_acc.data.borrow())?;
+    let mut src_pool_data = Pool::unpack(&src_pool_acc.data.borrow())?;
+    let dst_mint_lpt_data = Mint::unpack(&dst_mint_lpt_acc.data.borrow())?;
+    let mut dst_pool_data = Pool::unpack(&dst_pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    Self::is_member(&dst_pool_data, dst_pool_acc, owner, member_acc, program_id)?;
+    if src_pool_data.mint_lpt != *src_mint_lpt_acc.key
+      || src_pool_data.treasury_s != *src_treasury_s_acc.key
+      || src_pool_data.treasury_a != *src_treasury_a_acc.key
+      || src_pool_data.treasury_b != *src_treasury_b_acc.key
+      || dst_pool_data.mint_lpt != *dst_mint_lpt_acc.key
+      || dst_pool_data.treasury_s != *dst_treasury_s_acc.key
+      || dst_pool_data.treasury_a != *dst_treasury_a_acc.key
+      || dst_pool_data.treasury_b != *dst_treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if src_pool_data.is_frozen() {
+      return Err(AppError::FrozenPool.into());
+    }
+    if lpt == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    // Withdraw from the source pool
+    Self::record_reference(&mut src_pool_data, &clock);
+    let prev_src_pool_data = src_pool_data;
+    let (delta_s, delta_a, delta_b) = Oracle::unrake(
+      lpt,
+      src_pool_data.reserve_s,
+      src_pool_data.reserve_a,
+      src_pool_data.reserve_b,
+      src_mint_lpt_data.supply,
+    )
+    .ok_or(AppError::Overflow)?;
+    let withdrawals = [delta_s, delta_a, delta_b];
+    if !src_pool_data.is_emergency {
+      Self::track_outflow(&mut src_pool_data, withdrawals, clock.unix_timestamp)?;
+    }
+    src_pool_data.reserve_s = src_pool_data
+      .reserve_s
+      .checked_sub(delta_s)
+      .ok_or(AppError::Overflow)?;
+    src_pool_data.reserve_a = src_pool_data
+      .reserve_a
+      .checked_sub(delta_a)
+      .ok_or(AppError::Overflow)?;
+    src_pool_data.reserve_b = src_pool_data
+      .reserve_b
+      .checked_sub(delta_b)
+      .ok_or(AppError::Overflow)?;
+    if src_pool_data.reserve_s == 0 {
+      src_pool_data.state = PoolState::Frozen;
+    }
+    Self::is_share_kept(
+      &prev_src_pool_data,
+      src_mint_lpt_data.supply,
+      &src_pool_data,
+      src_mint_lpt_data
+        .supply
+        .checked_sub(lpt)
+        .ok_or(AppError::Overflow)?,
+    )?;
+
+    // Route each withdrawn token to the destination treasury of the same mint
+    let src_mints = [
+      src_pool_data.mint_s,
+      src_pool_data.mint_a,
+      src_pool_data.mint_b,
+    ];
+    let dst_mints = [
+      dst_pool_data.mint_s,
+      dst_pool_data.mint_a,
+      dst_pool_data.mint_b,
+    ];
+    let routes = src_mints.map(|mint| dst_mints.iter().position(|&dst_mint| dst_mint == mint));
+    let mut deltas = [0u64; 3];
+    for (&delta, route) in withdrawals.iter().zip(routes.iter()) {
+      if let Some(code) = route {
+        deltas[*code] = deltas[*code].checked_add(delta).ok_or(AppError::Overflow)?;
+      }
+    }
+
+    // Deposit into the destination pool
+    Self::record_reference(&mut dst_pool_data, &clock);
+    let prev_dst_pool_data = dst_pool_data;
+    let [delta_s, delta_a, delta_b] = deltas;
+    let (lpt_out, reserve_s, reserve_a, reserve_b) = Oracle::rake(
+      delta_s,
+      delta_a,
+      delta_b,
+      dst_pool_data.reserve_s,
+      dst_pool_data.reserve_a,
+      dst_pool_data.reserve_b,
+      dst_mint_lpt_data.supply,
+    )
+    .ok_or(AppError::Overflow)?;
+    if lpt_out < min_lpt_out {
+      return Err(AppError::ExceedLimit.into());
+    }
+    for &(delta, reserve, max_reserve) in [
+      (delta_s, reserve_s, dst_pool_data.max_reserve_s),
+      (delta_a, reserve_a, dst_pool_data.max_reserve_a),
+      (delta_b, reserve_b, dst_pool_data.max_reserve_b),
+    ]
+    .iter()
+    {
+      if delta > 0 && max_reserve != 0 && reserve > max_reserve {
+        return Err(AppError::ExceedReserveCap.into());
+      }
+    }
+    dst_pool_data.reserve_s = reserve_s;
+    dst_pool_data.reserve_a = reserve_a;
+    dst_pool_data.reserve_b = reserve_b;
+    Self::is_share_kept(
+      &prev_dst_pool_data,
+      dst_mint_lpt_data.supply,
+      &dst_pool_data,
+      dst_mint_lpt_data
+        .supply
+        .checked_add(lpt_out)
+        .ok_or(AppError::Overflow)?,
+    )?;
+
+    // Update pools
+    Pool::pack(src_pool_data, &mut src_pool_acc.data.borrow_mut())?;
+    Pool::pack(dst_pool_data, &mut dst_pool_acc.data.borrow_mut())?;
+    // Burn LPT
+    XSPLT::burn(
+      lpt,
+      src_lpt_acc,
+      src_mint_lpt_acc,
+      owner,
+      splt_program,
+      src_seed,
+    )?;
+    // Move tokens, refunding the ones the destination pool does not hold
+    let src_treasuries = [src_treasury_s_acc, src_treasury_a_acc, src_treasury_b_acc];
+    let dst_treasuries = [dst_treasury_s_acc, dst_treasury_a_acc, dst_treasury_b_acc];
+    let refunds = [refund_s_acc, refund_a_acc, refund_b_acc];
+    for (i, &amount) in withdrawals.iter().enumerate() {
+      let dst_acc = match routes[i] {
+        Some(code) => dst_treasuries[code],
+        None => refunds[i],
+      };
+      XSPLT::transfer(
+        amount,
+        src_treasuries[i],
+        dst_acc,
+        src_treasurer,
+        splt_program,
+        src_seed,
+      )?;
+    }
+    // Mint LPT
+    XSPLT::mint_to(
+      lpt_out,
+      dst_mint_lpt_acc,
+      dst_lpt_acc,
+      dst_treasurer,
+      splt_program,
+      dst_seed,
+    )?;
+    Self::is_covered(&src_pool_data, &src_treasuries)?;
+    Self::is_covered(&dst_pool_data, &dst_treasuries)?;
 
     Ok(())
   }
@@ -446,6 +1098,7 @@ impl Processor {
   pub fn swap(
     amount: u64,
     limit: u64,
+    flags: u8,
     program_id: &Pubkey,
     accounts: &[AccountInfo],
   ) -> ProgramResult {
@@ -464,100 +1117,145 @@ impl Processor {
 
     let treasurer = next_account_info(accounts_iter)?;
     let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    let (whitelist_acc, member_acc, referrer_acc) =
+      Self::split_optional_accounts(flags, accounts_iter)?;
 
     Self::is_program(program_id, &[pool_acc])?;
     Self::is_signer(&[payer])?;
 
     let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
     let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
-    let (bid_code, bid_reserve) = pool_data
-      .get_reserve(treasury_bid_acc.key)
-      .ok_or(AppError::UnmatchedPool)?;
-    let (ask_code, ask_reserve) = pool_data
-      .get_reserve(treasury_ask_acc.key)
-      .ok_or(AppError::UnmatchedPool)?;
-    let (sen_code, _) = pool_data
-      .get_reserve(treasury_sen_acc.key)
-      .ok_or(AppError::UnmatchedPool)?;
-    if sen_code != 0 {
-      return Err(AppError::UnmatchedPool.into());
-    }
-    if pool_data.is_frozen() {
-      return Err(AppError::FrozenPool.into());
-    }
-    if amount == 0 {
-      return Err(AppError::ZeroValue.into());
-    }
-    if *treasury_bid_acc.key == *treasury_ask_acc.key {
-      return Ok(());
-    }
-
-    // Compute new state
-    let new_bid_reserve = bid_reserve.checked_add(amount).ok_or(AppError::Overflow)?;
-    let (new_ask_reserve, paid_amount, earning) =
-      Oracle::curve_in_fee(new_bid_reserve, bid_reserve, ask_reserve, ask_code == 0)
-        .ok_or(AppError::Overflow)?;
-    if paid_amount < limit {
-      return Err(AppError::ExceedLimit.into());
-    }
+    let discount = Self::prepare_swap(
+      &pool_data,
+      payer,
+      pool_acc,
+      treasury_sen_acc,
+      (whitelist_acc, member_acc, referrer_acc),
+      program_id,
+    )?;
 
-    // Transfer bid
-    XSPLT::transfer(amount, src_acc, treasury_bid_acc, payer, splt_program, &[])?;
-    // Update bid pool data
-    match bid_code {
-      0 => pool_data.reserve_s = new_bid_reserve,
-      1 => pool_data.reserve_a = new_bid_reserve,
-      2 => pool_data.reserve_b = new_bid_reserve,
-      _ => return Err(AppError::UnmatchedPool.into()),
-    }
-    match ask_code {
-      0 => pool_data.reserve_s = new_ask_reserve,
-      1 => pool_data.reserve_a = new_ask_reserve,
-      2 => pool_data.reserve_b = new_ask_reserve,
-      _ => return Err(AppError::UnmatchedPool.into()),
-    }
-    // Transfer ask
-    XSPLT::transfer(
-      paid_amount,
-      treasury_ask_acc,
+    let mut leg_accs = vec![
+      payer,
+      pool_acc,
+      vault_acc,
+      src_acc,
+      treasury_bid_acc,
       dst_acc,
+      treasury_ask_acc,
+      treasury_sen_acc,
       treasurer,
       splt_program,
+    ];
+    leg_accs.extend(referrer_acc);
+    Self::swap_leg(
+      amount,
+      limit,
+      discount,
+      &mut pool_data,
+      &clock,
+      &leg_accs,
       seed,
     )?;
 
-    // Execute earning
-    if earning != 0 {
-      // Swap earning to SEN
-      let new_ask_reserve_with_earning = new_ask_reserve
-        .checked_add(earning)
-        .ok_or(AppError::Overflow)?;
-      let (new_sen_reserve, earning_in_sen, _) = Oracle::curve_in_fee(
-        new_ask_reserve_with_earning, // with earning
-        new_ask_reserve,              // without earning
-        pool_data.reserve_s,
-        true,
-      )
-      .ok_or(AppError::Overflow)?;
-      match ask_code {
-        1 => pool_data.reserve_a = new_ask_reserve_with_earning,
-        2 => pool_data.reserve_b = new_ask_reserve_with_earning,
-        _ => return Err(AppError::UnmatchedPool.into()),
-      }
-      pool_data.reserve_s = new_sen_reserve;
-      // Transfer earning
-      XSPLT::transfer(
-        earning_in_sen,
-        treasury_sen_acc,
+    // Save final data
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_bid_acc, treasury_ask_acc, treasury_sen_acc],
+    )?;
+
+    Ok(())
+  }
+
+  pub fn batch_swap(
+    legs: &[(u64, u64)],
+    flags: u8,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let vault_acc = next_account_info(accounts_iter)?;
+    let treasury_sen_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    // Source, bid treasury, destination and ask treasury of each leg
+    let mut leg_accs = Vec::with_capacity(legs.len());
+    for _ in legs.iter() {
+      leg_accs.push([
+        next_account_info(accounts_iter)?,
+        next_account_info(accounts_iter)?,
+        next_account_info(accounts_iter)?,
+        next_account_info(accounts_iter)?,
+      ]);
+    }
+    let (whitelist_acc, member_acc, referrer_acc) =
+      Self::split_optional_accounts(flags, accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[payer])?;
+    if legs.is_empty() {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    let discount = Self::prepare_swap(
+      &pool_data,
+      payer,
+      pool_acc,
+      treasury_sen_acc,
+      (whitelist_acc, member_acc, referrer_acc),
+      program_id,
+    )?;
+
+    // Run the legs in order against the same pool state
+    let mut paid_amounts = Vec::with_capacity(legs.len());
+    for (&(amount, limit), &[src_acc, treasury_bid_acc, dst_acc, treasury_ask_acc]) in
+      legs.iter().zip(leg_accs.iter())
+    {
+      let mut accs = vec![
+        payer,
+        pool_acc,
         vault_acc,
+        src_acc,
+        treasury_bid_acc,
+        dst_acc,
+        treasury_ask_acc,
+        treasury_sen_acc,
         treasurer,
         splt_program,
-        seed,
-      )?;
+      ];
+      accs.extend(referrer_acc);
+      let paid_amount =
+        Self::swap_leg(amount, limit, discount, &mut pool_data, &clock, &accs, seed)?;
+      paid_amounts.push(paid_amount);
     }
 
     // Save final data
     Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    let mut treasury_accs = vec![treasury_sen_acc];
+    for &[_, treasury_bid_acc, _, treasury_ask_acc] in leg_accs.iter() {
+      treasury_accs.push(treasury_bid_acc);
+      treasury_accs.push(treasury_ask_acc);
+    }
+    Self::is_covered(&pool_data, &treasury_accs)?;
+    // Callers read the paid amounts back as little-endian u64s
+    let return_data: Vec<u8> = paid_amounts
+      .iter()
+      .flat_map(|paid_amount| paid_amount.to_le_bytes())
+      .collect();
+    set_return_data(&return_data);
+    AppEvent::BatchSwap {
+      pool: *pool_acc.key,
+      paid_amounts,
+    }
+    .emit();
 
     Ok(())
   }
@@ -615,6 +1313,10 @@ impl Processor {
     if amount == 0 {
       return Err(AppError::ZeroValue.into());
     }
+    // Once shared, the vault is only paid out by DistributeEarnings
+    if pool_data.split != Pubkey::default() {
+      return Err(AppError::SharedVault.into());
+    }
     // Transfer earning
     XSPLT::transfer(amount, vault_acc, dst_acc, treasurer, splt_program, seed)?;
 
@@ -626,45 +1328,1978 @@ impl Processor {
     let owner = next_account_info(accounts_iter)?;
     let pool_acc = next_account_info(accounts_iter)?;
     let new_owner = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    // Queue the transfer, it will be applied by ExecuteAction
+    Self::queue_action(
+      Action::TransferPoolOwnership {
+        new_owner: *new_owner.key,
+      },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
 
-    Self::is_program(program_id, &[pool_acc])?;
+  pub fn execute_action(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    // The account written by the action, the pool itself if omitted
+    let target_acc = next_account_info(accounts_iter).unwrap_or(pool_acc);
+
+    Self::is_program(program_id, &[pool_acc, timelock_acc, target_acc])?;
     Self::is_signer(&[owner])?;
     Self::is_pool_owner(owner, pool_acc)?;
 
-    // Update pool data
     let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
-    pool_data.owner = *new_owner.key;
+    let timelock_data = Timelock::unpack(&timelock_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    if timelock_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if timelock_data.target != *target_acc.key {
+      return Err(AppError::UnmatchedTarget.into());
+    }
+    if !timelock_data.is_queued() {
+      return Err(AppError::InvalidAction.into());
+    }
+    if !timelock_data.is_ready(clock.unix_timestamp) {
+      return Err(AppError::Timelocked.into());
+    }
+    if timelock_data.is_expired(clock.unix_timestamp) {
+      return Err(AppError::ExpiredAction.into());
+    }
+
+    // Apply action to its target
+    match timelock_data.action {
+      Action::TransferPoolOwnership { new_owner } => pool_data.owner = new_owner,
+      Action::UpdateFees { fee_min, fee_max } => {
+        pool_data.fee_min = fee_min;
+        pool_data.fee_max = fee_max;
+      }
+      Action::SetDiscount { trader, discount } => {
+        let mut whitelist_data = Whitelist::unpack(&target_acc.data.borrow())?;
+        // A zero discount removes the trader
+        if !whitelist_data.set_discount(&trader, discount) {
+          return Err(AppError::FullWhitelist.into());
+        }
+        Whitelist::pack(whitelist_data, &mut target_acc.data.borrow_mut())?;
+      }
+      Action::UpdateReferral { referral } => pool_data.referral = referral,
+      Action::SetSplit { shares } => {
+        let mut split_data = Split::unpack(&target_acc.data.borrow())?;
+        split_data.shares = shares;
+        Split::pack(split_data, &mut target_acc.data.borrow_mut())?;
+        // From now on the vault is only paid out through the split
+        pool_data.split = *target_acc.key;
+      }
+      Action::UpdateRewardRate { reward_rate } => {
+        let mut farm_data = Farm::unpack(&target_acc.data.borrow())?;
+        // The past emission is accrued at the previous rate
+        Self::update_farm(&mut farm_data, &clock)?;
+        farm_data.reward_rate = reward_rate;
+        Farm::pack(farm_data, &mut target_acc.data.borrow_mut())?;
+      }
+      Action::UpdatePermission { is_permissioned } => pool_data.is_permissioned = is_permissioned,
+      Action::UpdateCaps {
+        max_reserve_s,
+        max_reserve_a,
+        max_reserve_b,
+        max_trade,
+      } => {
+        pool_data.max_reserve_s = max_reserve_s;
+        pool_data.max_reserve_a = max_reserve_a;
+        pool_data.max_reserve_b = max_reserve_b;
+        pool_data.max_trade = max_trade;
+      }
+      Action::UpdateCircuitBreaker { max_impact } => pool_data.max_impact = max_impact,
+      Action::UpdateRateLimit {
+        max_outflow,
+        outflow_window,
+      } => {
+        pool_data.max_outflow = max_outflow;
+        pool_data.outflow_window = outflow_window;
+        // The next outflow opens a new window
+        pool_data.window_start = i64::MIN;
+      }
+      Action::None => return Err(AppError::InvalidAction.into()),
+    }
     Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    // Close the timelock, refunding the rent
+    Self::close_timelock(owner, timelock_acc)?;
 
     Ok(())
   }
 
-  ///
-  /// Utilities
-  ///
+  pub fn cancel_action(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
 
-  pub fn is_program(program_id: &Pubkey, accounts: &[&AccountInfo]) -> ProgramResult {
-    for acc in &mut accounts.iter() {
-      if acc.owner != program_id {
-        return Err(AppError::IncorrectProgramId.into());
-      }
+    Self::is_program(program_id, &[pool_acc, timelock_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let timelock_data = Timelock::unpack(&timelock_acc.data.borrow())?;
+    if timelock_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if !timelock_data.is_queued() {
+      return Err(AppError::InvalidAction.into());
     }
+    // Close the timelock, refunding the rent
+    Self::close_timelock(owner, timelock_acc)?;
+
     Ok(())
   }
 
-  pub fn is_signer(accounts: &[&AccountInfo]) -> ProgramResult {
-    for acc in &mut accounts.iter() {
-      if !acc.is_signer {
-        return Err(AppError::InvalidOwner.into());
-      }
+  pub fn sync(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_acc = next_account_info(accounts_iter)?;
+    let treasury_s_acc = next_account_info(accounts_iter)?;
+    let treasury_a_acc = next_account_info(accounts_iter)?;
+    let treasury_b_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    if pool_data.treasury_s != *treasury_s_acc.key
+      || pool_data.treasury_a != *treasury_a_acc.key
+      || pool_data.treasury_b != *treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if pool_data.is_frozen() {
+      return Err(AppError::FrozenPool.into());
+    }
+
+    // Update pool data
+    Self::record_reference(&mut pool_data, &clock);
+    pool_data.reserve_s = Account::unpack(&treasury_s_acc.data.borrow())?.amount;
+    pool_data.reserve_a = Account::unpack(&treasury_a_acc.data.borrow())?.amount;
+    pool_data.reserve_b = Account::unpack(&treasury_b_acc.data.borrow())?.amount;
+    if pool_data.reserve_s == 0 || pool_data.reserve_a == 0 || pool_data.reserve_b == 0 {
+      return Err(AppError::ZeroValue.into());
     }
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+
     Ok(())
   }
 
-  pub fn is_pool_owner(owner: &AccountInfo, pool_acc: &AccountInfo) -> ProgramResult {
+  pub fn skim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+
+    let treasury_s_acc = next_account_info(accounts_iter)?;
+    let dst_s_acc = next_account_info(accounts_iter)?;
+
+    let treasury_a_acc = next_account_info(accounts_iter)?;
+    let dst_a_acc = next_account_info(accounts_iter)?;
+
+    let treasury_b_acc = next_account_info(accounts_iter)?;
+    let dst_b_acc = next_account_info(accounts_iter)?;
+
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
     let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
-    if pool_data.owner != *owner.key {
-      return Err(AppError::InvalidOwner.into());
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if pool_data.treasury_s != *treasury_s_acc.key
+      || pool_data.treasury_a != *treasury_a_acc.key
+      || pool_data.treasury_b != *treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+
+    // Compute the excess of each treasury
+    let excess_s = Account::unpack(&treasury_s_acc.data.borrow())?
+      .amount
+      .checked_sub(pool_data.reserve_s)
+      .ok_or(AppError::UncoveredReserve)?;
+    let excess_a = Account::unpack(&treasury_a_acc.data.borrow())?
+      .amount
+      .checked_sub(pool_data.reserve_a)
+      .ok_or(AppError::UncoveredReserve)?;
+    let excess_b = Account::unpack(&treasury_b_acc.data.borrow())?
+      .amount
+      .checked_sub(pool_data.reserve_b)
+      .ok_or(AppError::UncoveredReserve)?;
+    if excess_s == 0 && excess_a == 0 && excess_b == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    // Withdraw excess
+    if excess_s > 0 {
+      XSPLT::transfer(
+        excess_s,
+        treasury_s_acc,
+        dst_s_acc,
+        treasurer,
+        splt_program,
+        seed,
+      )?;
+    }
+    if excess_a > 0 {
+      XSPLT::transfer(
+        excess_a,
+        treasury_a_acc,
+        dst_a_acc,
+        treasurer,
+        splt_program,
+        seed,
+      )?;
+    }
+    if excess_b > 0 {
+      XSPLT::transfer(
+        excess_b,
+        treasury_b_acc,
+        dst_b_acc,
+        treasurer,
+        splt_program,
+        seed,
+      )?;
+    }
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
+
+    Ok(())
+  }
+
+  pub fn donate(
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let donor = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+
+    let src_s_acc = next_account_info(accounts_iter)?;
+    let treasury_s_acc = next_account_info(accounts_iter)?;
+
+    let src_a_acc = next_account_info(accounts_iter)?;
+    let treasury_a_acc = next_account_info(accounts_iter)?;
+
+    let src_b_acc = next_account_info(accounts_iter)?;
+    let treasury_b_acc = next_account_info(accounts_iter)?;
+
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[donor])?;
+
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    if pool_data.treasury_s != *treasury_s_acc.key
+      || pool_data.treasury_a != *treasury_a_acc.key
+      || pool_data.treasury_b != *treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if pool_data.is_frozen() {
+      return Err(AppError::FrozenPool.into());
+    }
+    if delta_s == 0 && delta_a == 0 && delta_b == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    // Deposit token
+    Self::record_reference(&mut pool_data, &clock);
+    if delta_s > 0 {
+      XSPLT::transfer(delta_s, src_s_acc, treasury_s_acc, donor, splt_program, &[])?;
+      pool_data.reserve_s = pool_data
+        .reserve_s
+        .checked_add(delta_s)
+        .ok_or(AppError::Overflow)?;
+    }
+    if delta_a > 0 {
+      XSPLT::transfer(delta_a, src_a_acc, treasury_a_acc, donor, splt_program, &[])?;
+      pool_data.reserve_a = pool_data
+        .reserve_a
+        .checked_add(delta_a)
+        .ok_or(AppError::Overflow)?;
+    }
+    if delta_b > 0 {
+      XSPLT::transfer(delta_b, src_b_acc, treasury_b_acc, donor, splt_program, &[])?;
+      pool_data.reserve_b = pool_data
+        .reserve_b
+        .checked_add(delta_b)
+        .ok_or(AppError::Overflow)?;
+    }
+    // Update pool
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
+    // Emit event
+    AppEvent::Donate {
+      pool: *pool_acc.key,
+      donor: *donor.key,
+      delta_s,
+      delta_a,
+      delta_b,
+    }
+    .emit();
+
+    Ok(())
+  }
+
+  pub fn close_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+    let burn_acc = next_account_info(accounts_iter)?;
+    let vault_acc = next_account_info(accounts_iter)?;
+
+    let treasury_s_acc = next_account_info(accounts_iter)?;
+    let dst_s_acc = next_account_info(accounts_iter)?;
+
+    let treasury_a_acc = next_account_info(accounts_iter)?;
+    let dst_a_acc = next_account_info(accounts_iter)?;
+
+    let treasury_b_acc = next_account_info(accounts_iter)?;
+    let dst_b_acc = next_account_info(accounts_iter)?;
+
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let mint_lpt_data = Mint::unpack(&mint_lpt_acc.data.borrow())?;
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if pool_data.mint_lpt != *mint_lpt_acc.key
+      || pool_data.burn != *burn_acc.key
+      || pool_data.vault != *vault_acc.key
+      || pool_data.treasury_s != *treasury_s_acc.key
+      || pool_data.treasury_a != *treasury_a_acc.key
+      || pool_data.treasury_b != *treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    // Drained once only the locked liquidity is left
+    let locked = Account::unpack(&burn_acc.data.borrow())?.amount;
+    if mint_lpt_data.supply > locked {
+      return Err(AppError::UndrainedPool.into());
+    }
+    // The earnings belong to the beneficiaries once the vault is shared
+    if pool_data.split != Pubkey::default() && Account::unpack(&vault_acc.data.borrow())?.amount > 0
+    {
+      return Err(AppError::SharedVault.into());
+    }
+
+    // Burn the locked liquidity and close the burn account
+    if locked > 0 {
+      XSPLT::burn(
+        locked,
+        burn_acc,
+        mint_lpt_acc,
+        treasurer,
+        splt_program,
+        seed,
+      )?;
+    }
+    XSPLT::close_account(burn_acc, owner, treasurer, splt_program, seed)?;
+    // Sweep dust and close token accounts
+    for (src_acc, dst_acc) in [
+      (treasury_s_acc, dst_s_acc),
+      (treasury_a_acc, dst_a_acc),
+      (treasury_b_acc, dst_b_acc),
+      (vault_acc, dst_s_acc),
+    ]
+    .iter()
+    {
+      let amount = Account::unpack(&src_acc.data.borrow())?.amount;
+      if amount > 0 {
+        XSPLT::transfer(amount, src_acc, dst_acc, treasurer, splt_program, seed)?;
+      }
+      XSPLT::close_account(src_acc, owner, treasurer, splt_program, seed)?;
+    }
+
+    // Close pool account
+    let lamports = owner
+      .lamports()
+      .checked_add(pool_acc.lamports())
+      .ok_or(AppError::Overflow)?;
+    **owner.lamports.borrow_mut() = lamports;
+    **pool_acc.lamports.borrow_mut() = 0;
+    for byte in pool_acc.data.borrow_mut().iter_mut() {
+      *byte = 0;
+    }
+
+    Ok(())
+  }
+
+  pub fn reseed_pool(
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let lpt_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+    let burn_acc = next_account_info(accounts_iter)?;
+
+    let src_s_acc = next_account_info(accounts_iter)?;
+    let mint_s_acc = next_account_info(accounts_iter)?;
+    let treasury_s_acc = next_account_info(accounts_iter)?;
+
+    let src_a_acc = next_account_info(accounts_iter)?;
+    let mint_a_acc = next_account_info(accounts_iter)?;
+    let treasury_a_acc = next_account_info(accounts_iter)?;
+
+    let src_b_acc = next_account_info(accounts_iter)?;
+    let mint_b_acc = next_account_info(accounts_iter)?;
+    let treasury_b_acc = next_account_info(accounts_iter)?;
+
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let mint_lpt_data = Mint::unpack(&mint_lpt_acc.data.borrow())?;
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if pool_data.mint_lpt != *mint_lpt_acc.key
+      || pool_data.burn != *burn_acc.key
+      || pool_data.mint_s != *mint_s_acc.key
+      || pool_data.mint_a != *mint_a_acc.key
+      || pool_data.mint_b != *mint_b_acc.key
+      || pool_data.treasury_s != *treasury_s_acc.key
+      || pool_data.treasury_a != *treasury_a_acc.key
+      || pool_data.treasury_b != *treasury_b_acc.key
+    {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    // Drained once only the locked liquidity is left
+    let locked = Account::unpack(&burn_acc.data.borrow())?.amount;
+    if mint_lpt_data.supply > locked {
+      return Err(AppError::UndrainedPool.into());
+    }
+    if reserve_s == 0 || reserve_a == 0 || reserve_b == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    Self::is_minimum_reserve(
+      &[reserve_s, reserve_a, reserve_b],
+      &[mint_s_acc, mint_a_acc, mint_b_acc],
+      pool_data.reserve_precision,
+    )?;
+
+    // Deposit token
+    Self::record_reference(&mut pool_data, &clock);
+    XSPLT::transfer(
+      reserve_s,
+      src_s_acc,
+      treasury_s_acc,
+      owner,
+      splt_program,
+      &[],
+    )?;
+    XSPLT::transfer(
+      reserve_a,
+      src_a_acc,
+      treasury_a_acc,
+      owner,
+      splt_program,
+      &[],
+    )?;
+    XSPLT::transfer(
+      reserve_b,
+      src_b_acc,
+      treasury_b_acc,
+      owner,
+      splt_program,
+      &[],
+    )?;
+    // Update pool data, the dust left by the last provider is merged into the new reserves
+    pool_data.state = PoolState::Initialized;
+    pool_data.reserve_s = pool_data
+      .reserve_s
+      .checked_add(reserve_s)
+      .ok_or(AppError::Overflow)?;
+    pool_data.reserve_a = pool_data
+      .reserve_a
+      .checked_add(reserve_a)
+      .ok_or(AppError::Overflow)?;
+    pool_data.reserve_b = pool_data
+      .reserve_b
+      .checked_add(reserve_b)
+      .ok_or(AppError::Overflow)?;
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+    // Lock the minimum liquidity of pools migrated without it
+    let locking = MINIMUM_LIQUIDITY.saturating_sub(locked);
+    if locking > 0 {
+      XSPLT::mint_to(
+        locking,
+        mint_lpt_acc,
+        burn_acc,
+        treasurer,
+        splt_program,
+        seed,
+      )?;
+    }
+    // Mint LPT up to the initial supply, the locked liquidity is kept
+    XSPLT::mint_to(
+      pool_data
+        .reserve_s
+        .checked_sub(mint_lpt_data.supply)
+        .and_then(|lpt| lpt.checked_sub(locking))
+        .ok_or(AppError::InsufficientLiquidity)?,
+      mint_lpt_acc,
+      lpt_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
+    Self::is_covered(
+      &pool_data,
+      &[treasury_s_acc, treasury_a_acc, treasury_b_acc],
+    )?;
+
+    Ok(())
+  }
+
+  pub fn update_fees(
+    fee_min: u64,
+    fee_max: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    if fee_min > fee_max || fee_max > MAX_FEE {
+      return Err(AppError::InvalidFee.into());
+    }
+
+    // Queue the fee bounds, they will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdateFees { fee_min, fee_max },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn initialize_whitelist(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let whitelist_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, whitelist_acc])?;
+    Self::is_signer(&[owner, whitelist_acc])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let mut whitelist_data = Whitelist::unpack_unchecked(&whitelist_acc.data.borrow())?;
+    if whitelist_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+
+    // Update whitelist data
+    whitelist_data.pool = *pool_acc.key;
+    whitelist_data.is_initialized = true;
+    Whitelist::pack(whitelist_data, &mut whitelist_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn set_discount(
+    discount: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let whitelist_acc = next_account_info(accounts_iter)?;
+    let trader = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[whitelist_acc])?;
+
+    let whitelist_data = Whitelist::unpack(&whitelist_acc.data.borrow())?;
+    if whitelist_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if discount > DECIMALS {
+      return Err(AppError::InvalidFee.into());
+    }
+
+    // Queue the discount, it will be applied to the whitelist by ExecuteAction
+    Self::queue_action(
+      Action::SetDiscount {
+        trader: *trader.key,
+        discount,
+      },
+      whitelist_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn update_referral(
+    referral: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    if referral > DECIMALS {
+      return Err(AppError::InvalidRatio.into());
+    }
+
+    // Queue the referral ratio, it will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdateReferral { referral },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn set_split(ratios: &[u64], program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let split_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+    let beneficiary_accs = accounts_iter.as_slice();
+
+    Self::is_program(program_id, &[pool_acc, split_acc])?;
+
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let mut split_data = Split::unpack_unchecked(&split_acc.data.borrow())?;
+    // A new split must be signed, an existing one must belong to the pool
+    if !split_data.is_initialized() {
+      Self::is_signer(&[split_acc])?;
+    } else if split_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if ratios.is_empty()
+      || ratios.len() > MAX_BENEFICIARIES
+      || ratios.len() != beneficiary_accs.len()
+    {
+      return Err(AppError::InvalidBeneficiary.into());
+    }
+    // Every ratio is used and the vault is fully shared
+    let mut total: u64 = 0;
+    for &ratio in ratios {
+      if ratio == 0 {
+        return Err(AppError::InvalidRatio.into());
+      }
+      total = total.checked_add(ratio).ok_or(AppError::Overflow)?;
+    }
+    if total != DECIMALS {
+      return Err(AppError::InvalidRatio.into());
+    }
+    // Beneficiaries receive the vault token
+    for beneficiary_acc in beneficiary_accs {
+      if Account::unpack(&beneficiary_acc.data.borrow())?.mint != pool_data.mint_s {
+        return Err(AppError::InvalidMint.into());
+      }
+    }
+
+    let mut shares = [Share::default(); MAX_BENEFICIARIES];
+    for ((share, &ratio), beneficiary_acc) in shares.iter_mut().zip(ratios).zip(beneficiary_accs) {
+      *share = Share {
+        beneficiary: *beneficiary_acc.key,
+        ratio,
+      };
+    }
+    // A new split is bound to the pool now and gets its shares with the action
+    if !split_data.is_initialized() {
+      split_data.pool = *pool_acc.key;
+      split_data.is_initialized = true;
+      Split::pack(split_data, &mut split_acc.data.borrow_mut())?;
+    }
+
+    // Queue the shares, they will be applied to the split by ExecuteAction
+    Self::queue_action(
+      Action::SetSplit { shares },
+      split_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn distribute_earnings(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let pool_acc = next_account_info(accounts_iter)?;
+    let vault_acc = next_account_info(accounts_iter)?;
+    let split_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let beneficiary_accs = accounts_iter.as_slice();
+
+    Self::is_program(program_id, &[pool_acc, split_acc])?;
+
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let split_data = Split::unpack(&split_acc.data.borrow())?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if pool_data.vault != *vault_acc.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    if pool_data.split != *split_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    // Beneficiaries are passed in the order of the split
+    if split_data.beneficiaries().count() != beneficiary_accs.len()
+      || split_data
+        .beneficiaries()
+        .zip(beneficiary_accs)
+        .any(|(share, beneficiary_acc)| share.beneficiary != *beneficiary_acc.key)
+    {
+      return Err(AppError::InvalidBeneficiary.into());
+    }
+    let amount = Account::unpack(&vault_acc.data.borrow())?.amount;
+    if amount == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    // Pay out the vault, the rounding dust stays for the next distribution
+    for (share, beneficiary_acc) in split_data.beneficiaries().zip(beneficiary_accs) {
+      let portion = Oracle::portion(amount, share.ratio).ok_or(AppError::Overflow)?;
+      if portion != 0 {
+        XSPLT::transfer(
+          portion,
+          vault_acc,
+          beneficiary_acc,
+          treasurer,
+          splt_program,
+          seed,
+        )?;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn initialize_farm(
+    reward_rate: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let farm_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+    let treasury_lpt_acc = next_account_info(accounts_iter)?;
+    let mint_reward_acc = next_account_info(accounts_iter)?;
+    let treasury_reward_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let sysvar_rent_acc = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, farm_acc])?;
+    Self::is_signer(&[owner, farm_acc, treasury_lpt_acc, treasury_reward_acc])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let mut farm_data = Farm::unpack_unchecked(&farm_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    Self::safe_seed(pool_acc, treasurer, program_id)?;
+    if farm_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+    if pool_data.mint_lpt != *mint_lpt_acc.key {
+      return Err(AppError::InvalidMint.into());
+    }
+
+    // Initialize the LPT escrow
+    XSPLT::initialize_account(
+      treasury_lpt_acc,
+      mint_lpt_acc,
+      treasurer,
+      sysvar_rent_acc,
+      splt_program,
+      &[],
+    )?;
+    // Initialize the reward treasury, funded by plain transfers
+    XSPLT::initialize_account(
+      treasury_reward_acc,
+      mint_reward_acc,
+      treasurer,
+      sysvar_rent_acc,
+      splt_program,
+      &[],
+    )?;
+
+    // Update farm data
+    farm_data.pool = *pool_acc.key;
+    farm_data.is_initialized = true;
+    farm_data.treasury_lpt = *treasury_lpt_acc.key;
+    farm_data.mint_reward = *mint_reward_acc.key;
+    farm_data.treasury_reward = *treasury_reward_acc.key;
+    farm_data.reward_rate = reward_rate;
+    farm_data.reward_per_share = Decimal::zero();
+    farm_data.total_stake = 0;
+    farm_data.last_update = clock.unix_timestamp;
+    Farm::pack(farm_data, &mut farm_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn update_reward_rate(
+    reward_rate: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let farm_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[farm_acc])?;
+
+    let farm_data = Farm::unpack(&farm_acc.data.borrow())?;
+    if farm_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+
+    // Queue the rate, it will be applied to the farm by ExecuteAction
+    Self::queue_action(
+      Action::UpdateRewardRate { reward_rate },
+      farm_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn stake(amount: u64, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let farm_acc = next_account_info(accounts_iter)?;
+    let staker_acc = next_account_info(accounts_iter)?;
+    let src_lpt_acc = next_account_info(accounts_iter)?;
+    let treasury_lpt_acc = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[farm_acc, staker_acc])?;
+    Self::is_signer(&[owner])?;
+
+    let mut farm_data = Farm::unpack(&farm_acc.data.borrow())?;
+    let mut staker_data = Staker::unpack_unchecked(&staker_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    if farm_data.treasury_lpt != *treasury_lpt_acc.key {
+      return Err(AppError::UnmatchedFarm.into());
+    }
+    // A new staker must be signed, an existing one must belong to the owner
+    if !staker_data.is_initialized() {
+      Self::is_signer(&[staker_acc])?;
+      staker_data.farm = *farm_acc.key;
+      staker_data.owner = *owner.key;
+      staker_data.is_initialized = true;
+    }
+    Self::is_staker_owner(owner, farm_acc, &staker_data)?;
+    if amount == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    // Settle the reward before the stake changes
+    Self::update_farm(&mut farm_data, &clock)?;
+    Self::update_staker(&mut staker_data, &farm_data)?;
+    // Escrow LPT
+    XSPLT::transfer(
+      amount,
+      src_lpt_acc,
+      treasury_lpt_acc,
+      owner,
+      splt_program,
+      &[],
+    )?;
+    staker_data.amount = staker_data
+      .amount
+      .checked_add(amount)
+      .ok_or(AppError::Overflow)?;
+    farm_data.total_stake = farm_data
+      .total_stake
+      .checked_add(amount)
+      .ok_or(AppError::Overflow)?;
+    staker_data.debt =
+      Farming::debt(staker_data.amount, farm_data.reward_per_share).ok_or(AppError::Overflow)?;
+    Farm::pack(farm_data, &mut farm_acc.data.borrow_mut())?;
+    Staker::pack(staker_data, &mut staker_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn unstake(amount: u64, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let farm_acc = next_account_info(accounts_iter)?;
+    let staker_acc = next_account_info(accounts_iter)?;
+    let treasury_lpt_acc = next_account_info(accounts_iter)?;
+    let dst_lpt_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, farm_acc, staker_acc])?;
+    Self::is_signer(&[owner])?;
+
+    let mut farm_data = Farm::unpack(&farm_acc.data.borrow())?;
+    let mut staker_data = Staker::unpack(&staker_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if farm_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if farm_data.treasury_lpt != *treasury_lpt_acc.key {
+      return Err(AppError::UnmatchedFarm.into());
+    }
+    Self::is_staker_owner(owner, farm_acc, &staker_data)?;
+    if amount == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    if amount > staker_data.amount {
+      return Err(AppError::InsufficientFunds.into());
+    }
+
+    // Settle the reward before the stake changes
+    Self::update_farm(&mut farm_data, &clock)?;
+    Self::update_staker(&mut staker_data, &farm_data)?;
+    // Release LPT
+    XSPLT::transfer(
+      amount,
+      treasury_lpt_acc,
+      dst_lpt_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
+    staker_data.amount = staker_data
+      .amount
+      .checked_sub(amount)
+      .ok_or(AppError::Overflow)?;
+    farm_data.total_stake = farm_data
+      .total_stake
+      .checked_sub(amount)
+      .ok_or(AppError::Overflow)?;
+    staker_data.debt =
+      Farming::debt(staker_data.amount, farm_data.reward_per_share).ok_or(AppError::Overflow)?;
+    Farm::pack(farm_data, &mut farm_acc.data.borrow_mut())?;
+    Staker::pack(staker_data, &mut staker_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn harvest(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let farm_acc = next_account_info(accounts_iter)?;
+    let staker_acc = next_account_info(accounts_iter)?;
+    let treasury_reward_acc = next_account_info(accounts_iter)?;
+    let dst_reward_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, farm_acc, staker_acc])?;
+    Self::is_signer(&[owner])?;
+
+    let mut farm_data = Farm::unpack(&farm_acc.data.borrow())?;
+    let mut staker_data = Staker::unpack(&staker_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if farm_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if farm_data.treasury_reward != *treasury_reward_acc.key {
+      return Err(AppError::UnmatchedFarm.into());
+    }
+    Self::is_staker_owner(owner, farm_acc, &staker_data)?;
+
+    Self::update_farm(&mut farm_data, &clock)?;
+    Self::update_staker(&mut staker_data, &farm_data)?;
+    // An underfunded farm pays what it holds, the rest stays accrued
+    let balance = Account::unpack(&treasury_reward_acc.data.borrow())?.amount;
+    let reward = staker_data.reward.min(balance);
+    if reward == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    XSPLT::transfer(
+      reward,
+      treasury_reward_acc,
+      dst_reward_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
+    staker_data.reward = staker_data
+      .reward
+      .checked_sub(reward)
+      .ok_or(AppError::Overflow)?;
+    Farm::pack(farm_data, &mut farm_acc.data.borrow_mut())?;
+    Staker::pack(staker_data, &mut staker_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn lock_liquidity(
+    lpt: u64,
+    unlock_ts: i64,
+    schedule: LockSchedule,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let lock_acc = next_account_info(accounts_iter)?;
+    let src_lpt_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+    let escrow_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let sysvar_rent_acc = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, lock_acc])?;
+    Self::is_signer(&[owner, lock_acc, escrow_acc])?;
+
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    let mut lock_data = Lock::unpack_unchecked(&lock_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    Self::safe_seed(pool_acc, treasurer, program_id)?;
+    if lock_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+    if pool_data.mint_lpt != *mint_lpt_acc.key {
+      return Err(AppError::InvalidMint.into());
+    }
+    if lpt == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    if unlock_ts <= clock.unix_timestamp {
+      return Err(AppError::InvalidSchedule.into());
+    }
+
+    // Initialize the escrow, owned by the treasurer PDA
+    XSPLT::initialize_account(
+      escrow_acc,
+      mint_lpt_acc,
+      treasurer,
+      sysvar_rent_acc,
+      splt_program,
+      &[],
+    )?;
+    // Escrow LPT
+    XSPLT::transfer(lpt, src_lpt_acc, escrow_acc, owner, splt_program, &[])?;
+
+    // Update lock data
+    lock_data.pool = *pool_acc.key;
+    lock_data.owner = *owner.key;
+    lock_data.is_initialized = true;
+    lock_data.schedule = schedule;
+    lock_data.escrow = *escrow_acc.key;
+    lock_data.amount = lpt;
+    lock_data.released = 0;
+    lock_data.start_ts = clock.unix_timestamp;
+    lock_data.unlock_ts = unlock_ts;
+    Lock::pack(lock_data, &mut lock_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn unlock_liquidity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let lock_acc = next_account_info(accounts_iter)?;
+    let escrow_acc = next_account_info(accounts_iter)?;
+    let dst_lpt_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, lock_acc])?;
+    Self::is_signer(&[owner])?;
+
+    let mut lock_data = Lock::unpack(&lock_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    let seed: &[&[&[u8]]] = &[&[&Self::safe_seed(pool_acc, treasurer, program_id)?[..]]];
+    if lock_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if lock_data.owner != *owner.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    if lock_data.escrow != *escrow_acc.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+
+    // Release what vested since the last unlock
+    let vested = match lock_data.schedule {
+      LockSchedule::Cliff => {
+        Vesting::cliff(lock_data.amount, lock_data.unlock_ts, clock.unix_timestamp)
+      }
+      LockSchedule::Linear => Vesting::linear(
+        lock_data.amount,
+        lock_data.start_ts,
+        lock_data.unlock_ts,
+        clock.unix_timestamp,
+      )
+      .ok_or(AppError::Overflow)?,
+    };
+    let releasable = vested
+      .checked_sub(lock_data.released)
+      .ok_or(AppError::Overflow)?;
+    if releasable == 0 {
+      return Err(AppError::LockedLiquidity.into());
+    }
+    XSPLT::transfer(
+      releasable,
+      escrow_acc,
+      dst_lpt_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
+    lock_data.released = vested;
+    Lock::pack(lock_data, &mut lock_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn update_permission(
+    is_permissioned: bool,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    // Queue the permission, it will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdatePermission { is_permissioned },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn update_caps(
+    max_reserve_s: u64,
+    max_reserve_a: u64,
+    max_reserve_b: u64,
+    max_trade: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    if max_trade > DECIMALS {
+      return Err(AppError::InvalidRatio.into());
+    }
+
+    // Queue the caps, they will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdateCaps {
+        max_reserve_s,
+        max_reserve_a,
+        max_reserve_b,
+        max_trade,
+      },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn update_circuit_breaker(
+    max_impact: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    // Queue the price impact limit, it will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdateCircuitBreaker { max_impact },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn update_rate_limit(
+    max_outflow: u64,
+    outflow_window: u64,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let timelock_acc = next_account_info(accounts_iter)?;
+    let sysvar_clock_acc = next_account_info(accounts_iter)?;
+
+    if max_outflow > DECIMALS {
+      return Err(AppError::InvalidRatio.into());
+    }
+    if max_outflow != 0 && outflow_window == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+
+    // Queue the rate limit, it will be applied by ExecuteAction
+    Self::queue_action(
+      Action::UpdateRateLimit {
+        max_outflow,
+        outflow_window,
+      },
+      pool_acc.key,
+      owner,
+      pool_acc,
+      timelock_acc,
+      sysvar_clock_acc,
+      program_id,
+    )
+  }
+
+  pub fn update_emergency(
+    is_emergency: bool,
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+  ) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let mut pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    pool_data.is_emergency = is_emergency;
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn add_member(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let member = next_account_info(accounts_iter)?;
+    let member_acc = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let sysvar_rent_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let (address, bump) = Member::find_address(pool_acc.key, member.key, program_id);
+    if address != *member_acc.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    // The entry is created on the first addition, the pool owner pays the rent
+    if member_acc.owner != program_id {
+      let rent = Rent::from_account_info(sysvar_rent_acc)?;
+      let seed: &[&[&[u8]]] = &[&[pool_acc.key.as_ref(), member.key.as_ref(), &[bump]]];
+      XSYSTEM::create_account(
+        rent.minimum_balance(Member::LEN),
+        Member::LEN as u64,
+        program_id,
+        owner,
+        member_acc,
+        system_program,
+        seed,
+      )?;
+    }
+
+    let mut member_data = Member::unpack_unchecked(&member_acc.data.borrow())?;
+    if member_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+    member_data.pool = *pool_acc.key;
+    member_data.member = *member.key;
+    member_data.is_initialized = true;
+    Member::pack(member_data, &mut member_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  pub fn remove_member(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let member_acc = next_account_info(accounts_iter)?;
+
+    Self::is_program(program_id, &[pool_acc, member_acc])?;
+    Self::is_signer(&[owner])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let member_data = Member::unpack(&member_acc.data.borrow())?;
+    if member_data.pool != *pool_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+
+    // Close the entry, refunding the rent
+    let lamports = owner
+      .lamports()
+      .checked_add(member_acc.lamports())
+      .ok_or(AppError::Overflow)?;
+    **owner.lamports.borrow_mut() = lamports;
+    **member_acc.lamports.borrow_mut() = 0;
+    for byte in member_acc.data.borrow_mut().iter_mut() {
+      *byte = 0;
+    }
+
+    Ok(())
+  }
+
+  pub fn migrate_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let burn_acc = next_account_info(accounts_iter)?;
+    let mint_lpt_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let sysvar_rent_acc = next_account_info(accounts_iter)?;
+
+    // Anyone may migrate, so that providers can always leave a legacy pool
+    Self::is_program(program_id, &[pool_acc])?;
+    Self::is_signer(&[payer])?;
+
+    // Only the unversioned layout is read here, the current one fails to unpack
+    let mut pool_data = Pool::unpack_legacy(&pool_acc.data.borrow())?;
+    let burn_data = Account::unpack_unchecked(&burn_acc.data.borrow())?;
+    Self::safe_seed(pool_acc, treasurer, program_id)?;
+    if pool_data.mint_lpt != *mint_lpt_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    // The burn account is a fresh one, it can't be an escrow of other LPT
+    if burn_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+
+    // Initialize burn account
+    XSPLT::initialize_account(
+      burn_acc,
+      mint_lpt_acc,
+      treasurer,
+      sysvar_rent_acc,
+      splt_program,
+      &[],
+    )?;
+    // Grow the account, the payer pays the extra rent
+    let rent = Rent::from_account_info(sysvar_rent_acc)?;
+    let lamports = rent
+      .minimum_balance(Pool::LEN)
+      .saturating_sub(pool_acc.lamports());
+    if lamports > 0 {
+      XSYSTEM::transfer(lamports, payer, pool_acc, system_program, &[])?;
+    }
+    pool_acc.realloc(Pool::LEN, true)?;
+
+    // Update pool data, the fee was fixed before the layout was versioned
+    pool_data.version = POOL_VERSION;
+    pool_data.fee_min = FEE;
+    pool_data.fee_max = FEE;
+    pool_data.burn = *burn_acc.key;
+    pool_data.reserve_precision = RESERVE_PRECISION;
+    Pool::pack(pool_data, &mut pool_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  ///
+  /// Utilities
+  ///
+
+  pub fn is_program(program_id: &Pubkey, accounts: &[&AccountInfo]) -> ProgramResult {
+    for acc in &mut accounts.iter() {
+      if acc.owner != program_id {
+        return Err(AppError::IncorrectProgramId.into());
+      }
+    }
+    Ok(())
+  }
+
+  pub fn is_signer(accounts: &[&AccountInfo]) -> ProgramResult {
+    for acc in &mut accounts.iter() {
+      if !acc.is_signer {
+        return Err(AppError::InvalidOwner.into());
+      }
+    }
+    Ok(())
+  }
+
+  pub fn is_pool_owner(owner: &AccountInfo, pool_acc: &AccountInfo) -> ProgramResult {
+    let pool_data = Pool::unpack(&pool_acc.data.borrow())?;
+    if pool_data.owner != *owner.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    Ok(())
+  }
+
+  pub fn is_minimum_reserve(
+    reserves: &[u64],
+    mint_accs: &[&AccountInfo],
+    precision: u8,
+  ) -> ProgramResult {
+    for (reserve, mint_acc) in reserves.iter().zip(mint_accs.iter()) {
+      let mint_data = Mint::unpack(&mint_acc.data.borrow())?;
+      if *reserve < minimum_reserve(mint_data.decimals, precision) {
+        return Err(AppError::InsufficientLiquidity.into());
+      }
+    }
+    Ok(())
+  }
+
+  pub fn is_covered(pool_data: &Pool, treasury_accs: &[&AccountInfo]) -> ProgramResult {
+    for treasury_acc in &mut treasury_accs.iter() {
+      let (_, reserve) = pool_data
+        .get_reserve(treasury_acc.key)
+        .ok_or(AppError::UnmatchedPool)?;
+      let treasury_data = Account::unpack(&treasury_acc.data.borrow())?;
+      if reserve > treasury_data.amount {
+        return Err(AppError::UncoveredReserve.into());
+      }
+    }
+    Ok(())
+  }
+
+  pub fn is_product_kept(prev_pool_data: &Pool, pool_data: &Pool) -> ProgramResult {
+    if !Invariant::is_product_kept(
+      prev_pool_data.reserve_s,
+      prev_pool_data.reserve_a,
+      prev_pool_data.reserve_b,
+      pool_data.reserve_s,
+      pool_data.reserve_a,
+      pool_data.reserve_b,
+    ) {
+      return Err(AppError::InvariantViolated.into());
+    }
+    Ok(())
+  }
+
+  pub fn is_share_kept(
+    prev_pool_data: &Pool,
+    prev_reserve_lpt: u64,
+    pool_data: &Pool,
+    reserve_lpt: u64,
+  ) -> ProgramResult {
+    if !Invariant::is_share_kept(
+      (
+        prev_pool_data.reserve_s,
+        prev_pool_data.reserve_a,
+        prev_pool_data.reserve_b,
+      ),
+      prev_reserve_lpt,
+      (
+        pool_data.reserve_s,
+        pool_data.reserve_a,
+        pool_data.reserve_b,
+      ),
+      reserve_lpt,
+    ) {
+      return Err(AppError::InvariantViolated.into());
+    }
+    Ok(())
+  }
+
+  pub fn is_staker_owner(
+    owner: &AccountInfo,
+    farm_acc: &AccountInfo,
+    staker_data: &Staker,
+  ) -> ProgramResult {
+    if staker_data.farm != *farm_acc.key {
+      return Err(AppError::UnmatchedFarm.into());
+    }
+    if staker_data.owner != *owner.key {
+      return Err(AppError::InvalidOwner.into());
+    }
+    Ok(())
+  }
+
+  // Accrue the emission since the last update
+  pub fn update_farm(farm_data: &mut Farm, clock: &Clock) -> ProgramResult {
+    let elapsed = clock
+      .unix_timestamp
+      .saturating_sub(farm_data.last_update)
+      .max(0) as u64;
+    farm_data.reward_per_share = Farming::reward_per_share(
+      farm_data.reward_per_share,
+      farm_data.reward_rate,
+      elapsed,
+      farm_data.total_stake,
+    )
+    .ok_or(AppError::Overflow)?;
+    farm_data.last_update = farm_data.last_update.max(clock.unix_timestamp);
+    Ok(())
+  }
+
+  // Move the pending reward of the staker to its accrued reward
+  pub fn update_staker(staker_data: &mut Staker, farm_data: &Farm) -> ProgramResult {
+    let pending = Farming::pending(
+      staker_data.amount,
+      farm_data.reward_per_share,
+      staker_data.debt,
+    )
+    .ok_or(AppError::Overflow)?;
+    staker_data.reward = staker_data
+      .reward
+      .checked_add(pending)
+      .ok_or(AppError::Overflow)?;
+    staker_data.debt =
+      Farming::debt(staker_data.amount, farm_data.reward_per_share).ok_or(AppError::Overflow)?;
+    Ok(())
+  }
+
+  pub fn queue_action<'a>(
+    action: Action,
+    target: &Pubkey,
+    owner: &AccountInfo<'a>,
+    pool_acc: &AccountInfo<'a>,
+    timelock_acc: &AccountInfo<'a>,
+    sysvar_clock_acc: &AccountInfo<'a>,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    Self::is_program(program_id, &[pool_acc, timelock_acc])?;
+    Self::is_signer(&[owner, timelock_acc])?;
+    Self::is_pool_owner(owner, pool_acc)?;
+
+    let mut timelock_data = Timelock::unpack_unchecked(&timelock_acc.data.borrow())?;
+    let clock = Clock::from_account_info(sysvar_clock_acc)?;
+    if timelock_data.is_initialized() {
+      return Err(AppError::ConstructorOnce.into());
+    }
+
+    // Update timelock data
+    timelock_data.pool = *pool_acc.key;
+    timelock_data.target = *target;
+    timelock_data.state = TimelockState::Queued;
+    timelock_data.eta = clock
+      .unix_timestamp
+      .checked_add(TIMELOCK_DELAY)
+      .ok_or(AppError::Overflow)?;
+    timelock_data.action = action;
+    Timelock::pack(timelock_data, &mut timelock_acc.data.borrow_mut())?;
+
+    Ok(())
+  }
+
+  // The first change of the reserves in a slot records the reference price
+  pub fn record_reference(pool_data: &mut Pool, clock: &Clock) {
+    if pool_data.ref_slot != clock.slot {
+      pool_data.ref_slot = clock.slot;
+      pool_data.ref_reserve_s = pool_data.reserve_s;
+      pool_data.ref_reserve_a = pool_data.reserve_a;
+      pool_data.ref_reserve_b = pool_data.reserve_b;
+    }
+  }
+
+  pub fn close_timelock(owner: &AccountInfo, timelock_acc: &AccountInfo) -> ProgramResult {
+    let lamports = owner
+      .lamports()
+      .checked_add(timelock_acc.lamports())
+      .ok_or(AppError::Overflow)?;
+    **owner.lamports.borrow_mut() = lamports;
+    **timelock_acc.lamports.borrow_mut() = 0;
+    for byte in timelock_acc.data.borrow_mut().iter_mut() {
+      *byte = 0;
+    }
+
+    Ok(())
+  }
+
+  // Record the outflows of the reserves, each limited to a part of the reserve
+  // at the start of the window, zero is unlimited
+  pub fn track_outflow(pool_data: &mut Pool, outflows: [u64; 3], now: i64) -> ProgramResult {
+    if pool_data.max_outflow == 0 {
+      return Ok(());
+    }
+    let elapsed = now.saturating_sub(pool_data.window_start).max(0) as u64;
+    if elapsed >= pool_data.outflow_window {
+      pool_data.window_start = now;
+      pool_data.window_reserve_s = pool_data.reserve_s;
+      pool_data.window_reserve_a = pool_data.reserve_a;
+      pool_data.window_reserve_b = pool_data.reserve_b;
+      pool_data.outflow_s = 0;
+      pool_data.outflow_a = 0;
+      pool_data.outflow_b = 0;
+    }
+    let [delta_s, delta_a, delta_b] = outflows;
+    pool_data.outflow_s = pool_data
+      .outflow_s
+      .checked_add(delta_s)
+      .ok_or(AppError::Overflow)?;
+    pool_data.outflow_a = pool_data
+      .outflow_a
+      .checked_add(delta_a)
+      .ok_or(AppError::Overflow)?;
+    pool_data.outflow_b = pool_data
+      .outflow_b
+      .checked_add(delta_b)
+      .ok_or(AppError::Overflow)?;
+    for &(outflow, reserve) in [
+      (pool_data.outflow_s, pool_data.window_reserve_s),
+      (pool_data.outflow_a, pool_data.window_reserve_a),
+      (pool_data.outflow_b, pool_data.window_reserve_b),
+    ]
+    .iter()
+    {
+      let limit = Oracle::portion(reserve, pool_data.max_outflow).ok_or(AppError::Overflow)?;
+      if outflow > limit {
+        return Err(AppError::ExceedOutflow.into());
+      }
+    }
+    Ok(())
+  }
+
+  // Whitelist, allowlist entry and referrer of a swap, each passed only if flagged
+  pub fn split_optional_accounts<'a, 'b>(
+    flags: u8,
+    accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+  ) -> Result<OptionalAccounts<'a, 'b>, ProgramError> {
+    let mut next_if = |flag: u8| match flags & flag {
+      0 => Ok(None),
+      _ => next_account_info(accounts_iter).map(Some),
+    };
+    Ok((
+      next_if(WITH_WHITELIST)?,
+      next_if(WITH_MEMBER)?,
+      next_if(WITH_REFERRER)?,
+    ))
+  }
+
+  // Checks shared by every swap of a transaction, returns the discount of the payer
+  pub fn prepare_swap(
+    pool_data: &Pool,
+    payer: &AccountInfo,
+    pool_acc: &AccountInfo,
+    treasury_sen_acc: &AccountInfo,
+    (whitelist_acc, member_acc, referrer_acc): OptionalAccounts,
+    program_id: &Pubkey,
+  ) -> Result<u64, ProgramError> {
+    Self::is_member(pool_data, pool_acc, payer, member_acc, program_id)?;
+    if let Some(referrer_acc) = referrer_acc {
+      let referrer_data = Account::unpack(&referrer_acc.data.borrow())?;
+      if referrer_data.mint != pool_data.mint_s {
+        return Err(AppError::InvalidMint.into());
+      }
+      // The referral would only be a rebate on the payer's own fee
+      if referrer_data.owner == *payer.key {
+        return Err(AppError::SelfReferral.into());
+      }
+    }
+    let discount = match whitelist_acc {
+      Some(whitelist_acc) => {
+        Self::is_program(program_id, &[whitelist_acc])?;
+        let whitelist_data = Whitelist::unpack(&whitelist_acc.data.borrow())?;
+        if whitelist_data.pool != *pool_acc.key {
+          return Err(AppError::UnmatchedPool.into());
+        }
+        whitelist_data.get_discount(payer.key)
+      }
+      None => 0,
+    };
+    if pool_data.treasury_s != *treasury_sen_acc.key {
+      return Err(AppError::UnmatchedPool.into());
+    }
+    if pool_data.is_frozen() {
+      return Err(AppError::FrozenPool.into());
+    }
+    Ok(discount)
+  }
+
+  // Run a swap against the unpacked pool, the caller packs it and checks the treasuries
+  // Return the paid amount
+  pub fn swap_leg(
+    amount: u64,
+    limit: u64,
+    discount: u64,
+    pool_data: &mut Pool,
+    clock: &Clock,
+    accounts: &[&AccountInfo],
+    seed: &[&[&[u8]]],
+  ) -> Result<u64, ProgramError> {
+    let accounts_iter = &mut accounts.iter().copied();
+    let payer = next_account_info(accounts_iter)?;
+    let pool_acc = next_account_info(accounts_iter)?;
+    let vault_acc = next_account_info(accounts_iter)?;
+    let src_acc = next_account_info(accounts_iter)?;
+    let treasury_bid_acc = next_account_info(accounts_iter)?;
+    let dst_acc = next_account_info(accounts_iter)?;
+    let treasury_ask_acc = next_account_info(accounts_iter)?;
+    let treasury_sen_acc = next_account_info(accounts_iter)?;
+    let treasurer = next_account_info(accounts_iter)?;
+    let splt_program = next_account_info(accounts_iter)?;
+    let referrer_acc = accounts_iter.next();
+
+    let (bid_code, bid_reserve) = pool_data
+      .get_reserve(treasury_bid_acc.key)
+      .ok_or(AppError::UnmatchedPool)?;
+    let (ask_code, ask_reserve) = pool_data
+      .get_reserve(treasury_ask_acc.key)
+      .ok_or(AppError::UnmatchedPool)?;
+    if amount == 0 {
+      return Err(AppError::ZeroValue.into());
+    }
+    // The input is capped to a part of the bid reserve, zero is uncapped
+    if pool_data.max_trade != 0
+      && amount > Oracle::portion(bid_reserve, pool_data.max_trade).ok_or(AppError::Overflow)?
+    {
+      return Err(AppError::ExceedTradeCap.into());
+    }
+    if *treasury_bid_acc.key == *treasury_ask_acc.key {
+      return Ok(0);
+    }
+    Self::record_reference(pool_data, clock);
+
+    // The volatility fades while the pool is idle
+    pool_data.volatility = Oracle::decay_volatility(
+      pool_data.volatility,
+      clock.unix_timestamp.saturating_sub(pool_data.volatility_ts),
+    );
+    pool_data.volatility_ts = clock.unix_timestamp;
+
+    // Compute new state
+    let prev_pool_data = *pool_data;
+    let fee = Oracle::fee(pool_data.volatility, pool_data.fee_min, pool_data.fee_max);
+    let new_bid_reserve = bid_reserve.checked_add(amount).ok_or(AppError::Overflow)?;
+    let (new_ask_reserve, paid_amount, earning) = Oracle::curve_in_fee(
+      new_bid_reserve,
+      bid_reserve,
+      ask_reserve,
+      fee,
+      discount,
+      ask_code == 0,
+    )
+    .ok_or(AppError::Overflow)?;
+    if paid_amount < limit {
+      return Err(AppError::ExceedLimit.into());
+    }
+    let mut outflows = [0; 3];
+    outflows[ask_code as usize] = paid_amount;
+    Self::track_outflow(pool_data, outflows, clock.unix_timestamp)?;
+
+    // Transfer bid
+    XSPLT::transfer(amount, src_acc, treasury_bid_acc, payer, splt_program, &[])?;
+    // Update bid pool data
+    match bid_code {
+      0 => pool_data.reserve_s = new_bid_reserve,
+      1 => pool_data.reserve_a = new_bid_reserve,
+      2 => pool_data.reserve_b = new_bid_reserve,
+      _ => return Err(AppError::UnmatchedPool.into()),
+    }
+    match ask_code {
+      0 => pool_data.reserve_s = new_ask_reserve,
+      1 => pool_data.reserve_a = new_ask_reserve,
+      2 => pool_data.reserve_b = new_ask_reserve,
+      _ => return Err(AppError::UnmatchedPool.into()),
+    }
+    // Track the price move for the next fees
+    pool_data.volatility = Oracle::volatility(
+      pool_data.volatility,
+      bid_reserve,
+      ask_reserve,
+      new_bid_reserve,
+      new_ask_reserve,
+    )
+    .ok_or(AppError::Overflow)?;
+    // Transfer ask
+    XSPLT::transfer(
+      paid_amount,
+      treasury_ask_acc,
+      dst_acc,
+      treasurer,
+      splt_program,
+      seed,
+    )?;
+
+    // Execute earning
+    let mut earning_in_sen = 0;
+    let mut referral = 0;
+    if earning != 0 {
+      // Swap earning to SEN
+      let new_ask_reserve_with_earning = new_ask_reserve
+        .checked_add(earning)
+        .ok_or(AppError::Overflow)?;
+      let (new_sen_reserve, sen, _) = Oracle::curve_in_fee(
+        new_ask_reserve_with_earning, // with earning
+        new_ask_reserve,              // without earning
+        pool_data.reserve_s,
+        fee,
+        0,
+        true,
+      )
+      .ok_or(AppError::Overflow)?;
+      match ask_code {
+        1 => pool_data.reserve_a = new_ask_reserve_with_earning,
+        2 => pool_data.reserve_b = new_ask_reserve_with_earning,
+        _ => return Err(AppError::UnmatchedPool.into()),
+      }
+      pool_data.reserve_s = new_sen_reserve;
+      earning_in_sen = sen;
+      // Share the earning with the referrer
+      if let Some(referrer_acc) = referrer_acc {
+        referral = Oracle::portion(earning_in_sen, pool_data.referral).ok_or(AppError::Overflow)?;
+        if referral != 0 {
+          XSPLT::transfer(
+            referral,
+            treasury_sen_acc,
+            referrer_acc,
+            treasurer,
+            splt_program,
+            seed,
+          )?;
+        }
+      }
+      // Transfer earning
+      let earning_to_vault = earning_in_sen
+        .checked_sub(referral)
+        .ok_or(AppError::Overflow)?;
+      if earning_to_vault != 0 {
+        XSPLT::transfer(
+          earning_to_vault,
+          treasury_sen_acc,
+          vault_acc,
+          treasurer,
+          splt_program,
+          seed,
+        )?;
+      }
+      // The earning leaves the SEN reserve for the vault and the referrer
+      Self::track_outflow(pool_data, [earning_in_sen, 0, 0], clock.unix_timestamp)?;
+    }
+
+    // Circuit breaker on the price move within the slot, zero is disabled
+    if pool_data.max_impact != 0 {
+      let ref_reserves = pool_data.get_ref_reserves();
+      let reserves = pool_data.get_reserves();
+      let impact = Oracle::impact(
+        ref_reserves[bid_code as usize],
+        ref_reserves[ask_code as usize],
+        reserves[bid_code as usize],
+        reserves[ask_code as usize],
+      )
+      .ok_or(AppError::Overflow)?;
+      if impact > pool_data.max_impact {
+        return Err(AppError::ExceedPriceImpact.into());
+      }
+    }
+
+    Self::is_product_kept(&prev_pool_data, pool_data)?;
+    AppEvent::Swap {
+      pool: *pool_acc.key,
+      amount,
+      paid_amount,
+      earning,
+      earning_in_sen,
+      referrer: referrer_acc.map(|acc| *acc.key),
+      referral,
+    }
+    .emit();
+
+    Ok(paid_amount)
+  }
+
+  // Permissioned pools only serve the wallets in their allowlist
+  pub fn is_member(
+    pool_data: &Pool,
+    pool_acc: &AccountInfo,
+    wallet: &AccountInfo,
+    member_acc: Option<&AccountInfo>,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    if !pool_data.is_permissioned {
+      return Ok(());
+    }
+    let member_acc = member_acc.ok_or(AppError::NotMember)?;
+    if member_acc.owner != program_id {
+      return Err(AppError::NotMember.into());
+    }
+    let member_data = Member::unpack(&member_acc.data.borrow()).or(Err(AppError::NotMember))?;
+    if member_data.pool != *pool_acc.key || member_data.member != *wallet.key {
+      return Err(AppError::NotMember.into());
     }
     Ok(())
   }
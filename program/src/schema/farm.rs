@@ -0,0 +1,114 @@
+use crate::helper::math::{Decimal, U256};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+///
+/// Farm struct, LPT of the pool are staked for the reward token
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Farm {
+  pub pool: Pubkey,
+  pub is_initialized: bool,
+  pub treasury_lpt: Pubkey,
+  pub mint_reward: Pubkey,
+  pub treasury_reward: Pubkey,
+  pub reward_rate: u64, // Reward per second
+  pub reward_per_share: Decimal,
+  pub total_stake: u64,
+  pub last_update: i64,
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Farm {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Farm {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Farm {
+  // Fixed length
+  const LEN: usize = 32 + 1 + 32 + 32 + 32 + 8 + 32 + 8 + 8;
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read farm data");
+    let src = array_ref![src, 0, 185];
+    let (
+      pool,
+      is_initialized,
+      treasury_lpt,
+      mint_reward,
+      treasury_reward,
+      reward_rate,
+      reward_per_share,
+      total_stake,
+      last_update,
+    ) = array_refs![src, 32, 1, 32, 32, 32, 8, 32, 8, 8];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    Ok(Farm {
+      pool: Pubkey::new_from_array(*pool),
+      is_initialized,
+      treasury_lpt: Pubkey::new_from_array(*treasury_lpt),
+      mint_reward: Pubkey::new_from_array(*mint_reward),
+      treasury_reward: Pubkey::new_from_array(*treasury_reward),
+      reward_rate: u64::from_le_bytes(*reward_rate),
+      reward_per_share: Decimal(U256::from_little_endian(reward_per_share)),
+      total_stake: u64::from_le_bytes(*total_stake),
+      last_update: i64::from_le_bytes(*last_update),
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write farm data");
+    let dst = array_mut_ref![dst, 0, 185];
+    let (
+      dst_pool,
+      dst_is_initialized,
+      dst_treasury_lpt,
+      dst_mint_reward,
+      dst_treasury_reward,
+      dst_reward_rate,
+      dst_reward_per_share,
+      dst_total_stake,
+      dst_last_update,
+    ) = mut_array_refs![dst, 32, 1, 32, 32, 32, 8, 32, 8, 8];
+    let &Farm {
+      ref pool,
+      is_initialized,
+      ref treasury_lpt,
+      ref mint_reward,
+      ref treasury_reward,
+      reward_rate,
+      reward_per_share,
+      total_stake,
+      last_update,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+    dst_treasury_lpt.copy_from_slice(treasury_lpt.as_ref());
+    dst_mint_reward.copy_from_slice(mint_reward.as_ref());
+    dst_treasury_reward.copy_from_slice(treasury_reward.as_ref());
+    *dst_reward_rate = reward_rate.to_le_bytes();
+    reward_per_share.0.to_little_endian(dst_reward_per_share);
+    *dst_total_stake = total_stake.to_le_bytes();
+    *dst_last_update = last_update.to_le_bytes();
+  }
+}
@@ -0,0 +1,123 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_enum::TryFromPrimitive;
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+///
+/// Lock schedule
+///
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum LockSchedule {
+  Cliff,  // Everything is released at the unlock time
+  Linear, // Released pro rata from the lock time to the unlock time
+}
+// Not derived, num_enum would take a #[default] variant as the catch-all
+#[allow(clippy::derivable_impls)]
+impl Default for LockSchedule {
+  fn default() -> Self {
+    LockSchedule::Cliff
+  }
+}
+
+///
+/// Lock struct, LPT escrowed until the unlock time
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lock {
+  pub pool: Pubkey,
+  pub owner: Pubkey,
+  pub is_initialized: bool,
+  pub schedule: LockSchedule,
+  pub escrow: Pubkey,
+  pub amount: u64,
+  pub released: u64,
+  pub start_ts: i64,
+  pub unlock_ts: i64,
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Lock {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Lock {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Lock {
+  // Fixed length
+  const LEN: usize = 32 + 32 + 1 + 1 + 32 + 8 + 8 + 8 + 8;
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read lock data");
+    let src = array_ref![src, 0, 130];
+    let (pool, owner, is_initialized, schedule, escrow, amount, released, start_ts, unlock_ts) =
+      array_refs![src, 32, 32, 1, 1, 32, 8, 8, 8, 8];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    Ok(Lock {
+      pool: Pubkey::new_from_array(*pool),
+      owner: Pubkey::new_from_array(*owner),
+      is_initialized,
+      schedule: LockSchedule::try_from_primitive(schedule[0])
+        .or(Err(ProgramError::InvalidAccountData))?,
+      escrow: Pubkey::new_from_array(*escrow),
+      amount: u64::from_le_bytes(*amount),
+      released: u64::from_le_bytes(*released),
+      start_ts: i64::from_le_bytes(*start_ts),
+      unlock_ts: i64::from_le_bytes(*unlock_ts),
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write lock data");
+    let dst = array_mut_ref![dst, 0, 130];
+    let (
+      dst_pool,
+      dst_owner,
+      dst_is_initialized,
+      dst_schedule,
+      dst_escrow,
+      dst_amount,
+      dst_released,
+      dst_start_ts,
+      dst_unlock_ts,
+    ) = mut_array_refs![dst, 32, 32, 1, 1, 32, 8, 8, 8, 8];
+    let &Lock {
+      ref pool,
+      ref owner,
+      is_initialized,
+      schedule,
+      ref escrow,
+      amount,
+      released,
+      start_ts,
+      unlock_ts,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    dst_owner.copy_from_slice(owner.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+    *dst_schedule = [schedule as u8];
+    dst_escrow.copy_from_slice(escrow.as_ref());
+    *dst_amount = amount.to_le_bytes();
+    *dst_released = released.to_le_bytes();
+    *dst_start_ts = start_ts.to_le_bytes();
+    *dst_unlock_ts = unlock_ts.to_le_bytes();
+  }
+}
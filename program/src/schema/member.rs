@@ -0,0 +1,80 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+///
+/// Member struct, the allowlist entry of a permissioned pool
+/// The account lives at the address derived from the pool and the member
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Member {
+  pub pool: Pubkey,
+  pub member: Pubkey,
+  pub is_initialized: bool,
+}
+
+///
+/// Member implementation
+///
+impl Member {
+  // Address of the allowlist entry
+  pub fn find_address(pool: &Pubkey, member: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref(), member.as_ref()], program_id)
+  }
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Member {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Member {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Member {
+  // Fixed length
+  const LEN: usize = 32 + 32 + 1;
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read member data");
+    let src = array_ref![src, 0, 65];
+    let (pool, member, is_initialized) = array_refs![src, 32, 32, 1];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    Ok(Member {
+      pool: Pubkey::new_from_array(*pool),
+      member: Pubkey::new_from_array(*member),
+      is_initialized,
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write member data");
+    let dst = array_mut_ref![dst, 0, 65];
+    let (dst_pool, dst_member, dst_is_initialized) = mut_array_refs![dst, 32, 32, 1];
+    let &Member {
+      ref pool,
+      ref member,
+      is_initialized,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    dst_member.copy_from_slice(member.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+  }
+}
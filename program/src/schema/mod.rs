@@ -1,3 +1,10 @@
 pub mod account;
+pub mod farm;
+pub mod lock;
+pub mod member;
 pub mod mint;
 pub mod pool;
+pub mod split;
+pub mod staker;
+pub mod timelock;
+pub mod whitelist;
@@ -7,6 +7,17 @@ use solana_program::{
   pubkey::Pubkey,
 };
 
+pub const MINIMUM_LIQUIDITY: u64 = 1000; // LPT locked forever at initialization
+pub const POOL_VERSION: u8 = 1; // Layout version, zero for pools yet to be initialized
+pub const LEGACY_LEN: usize = 32 + 1 + 32 + 32 + 3 * (32 + 32 + 8); // Unversioned layout
+
+pub const RESERVE_PRECISION: u8 = 3; // Default minimum reserve, a thousandth of one whole token
+
+// Minimum initial reserve of a token, one whole token over 10^precision
+pub fn minimum_reserve(decimals: u8, precision: u8) -> u64 {
+  10u64.saturating_pow(decimals.saturating_sub(precision) as u32)
+}
+
 ///
 /// Pool state
 ///
@@ -44,6 +55,41 @@ pub struct Pool {
   pub mint_b: Pubkey,
   pub treasury_b: Pubkey,
   pub reserve_b: u64,
+
+  pub version: u8,
+
+  pub fee_min: u64,
+  pub fee_max: u64,
+  pub volatility: u64,
+  pub referral: u64,
+  pub is_permissioned: bool,
+
+  pub max_reserve_s: u64,
+  pub max_reserve_a: u64,
+  pub max_reserve_b: u64,
+  pub max_trade: u64,
+
+  pub max_impact: u64,
+  pub ref_slot: u64,
+  pub ref_reserve_s: u64,
+  pub ref_reserve_a: u64,
+  pub ref_reserve_b: u64,
+
+  pub max_outflow: u64,
+  pub outflow_window: u64,
+  pub window_start: i64,
+  pub window_reserve_s: u64,
+  pub window_reserve_a: u64,
+  pub window_reserve_b: u64,
+  pub outflow_s: u64,
+  pub outflow_a: u64,
+  pub outflow_b: u64,
+  pub is_emergency: bool,
+
+  pub burn: Pubkey,
+  pub split: Pubkey,
+  pub reserve_precision: u8,
+  pub volatility_ts: i64,
 }
 
 ///
@@ -54,6 +100,13 @@ impl Pool {
   pub fn is_frozen(&self) -> bool {
     self.state == PoolState::Frozen
   }
+  // Reserves by code, with the reserves at the start of the reference slot
+  pub fn get_reserves(&self) -> [u64; 3] {
+    [self.reserve_s, self.reserve_a, self.reserve_b]
+  }
+  pub fn get_ref_reserves(&self) -> [u64; 3] {
+    [self.ref_reserve_s, self.ref_reserve_a, self.ref_reserve_b]
+  }
   // Verify the pair of mint and treasury
   // 0: None, 1: S pool, 2: A pool, 3: B pool
   pub fn get_reserve(&self, treasury: &Pubkey) -> Option<(u8, u64)> {
@@ -69,6 +122,44 @@ impl Pool {
 
     None
   }
+  // Read the unversioned layout, the later fields are left to their defaults
+  pub fn unpack_legacy(src: &[u8]) -> Result<Self, ProgramError> {
+    if src.len() != LEGACY_LEN {
+      return Err(ProgramError::InvalidAccountData);
+    }
+    let src = array_ref![src, 0, LEGACY_LEN];
+    let (
+      owner,
+      state,
+      mint_lpt,
+      vault,
+      mint_s,
+      treasury_s,
+      reserve_s,
+      mint_a,
+      treasury_a,
+      reserve_a,
+      mint_b,
+      treasury_b,
+      reserve_b,
+    ) = array_refs![src, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8];
+    Ok(Pool {
+      owner: Pubkey::new_from_array(*owner),
+      state: PoolState::try_from_primitive(state[0]).or(Err(ProgramError::InvalidAccountData))?,
+      mint_lpt: Pubkey::new_from_array(*mint_lpt),
+      vault: Pubkey::new_from_array(*vault),
+      mint_s: Pubkey::new_from_array(*mint_s),
+      treasury_s: Pubkey::new_from_array(*treasury_s),
+      reserve_s: u64::from_le_bytes(*reserve_s),
+      mint_a: Pubkey::new_from_array(*mint_a),
+      treasury_a: Pubkey::new_from_array(*treasury_a),
+      reserve_a: u64::from_le_bytes(*reserve_a),
+      mint_b: Pubkey::new_from_array(*mint_b),
+      treasury_b: Pubkey::new_from_array(*treasury_b),
+      reserve_b: u64::from_le_bytes(*reserve_b),
+      ..Pool::default()
+    })
+  }
 }
 
 ///
@@ -90,11 +181,12 @@ impl IsInitialized for Pool {
 ///
 impl Pack for Pool {
   // Fixed length
-  const LEN: usize = 32 + 1 + 32 + 32 + 3 * (32 + 32 + 8);
+  const LEN: usize =
+    LEGACY_LEN + 1 + 8 + 8 + 8 + 8 + 1 + 4 * 8 + 5 * 8 + 9 * 8 + 1 + 32 + 32 + 1 + 8;
   // Unpack data from [u8] to the data struct
   fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
     msg!("Read pool data");
-    let src = array_ref![src, 0, 313];
+    let src = array_ref![src, 0, 565];
     let (
       owner,
       state,
@@ -109,10 +201,58 @@ impl Pack for Pool {
       mint_b,
       treasury_b,
       reserve_b,
-    ) = array_refs![src, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8];
+      version,
+      fee_min,
+      fee_max,
+      volatility,
+      referral,
+      is_permissioned,
+      max_reserve_s,
+      max_reserve_a,
+      max_reserve_b,
+      max_trade,
+      max_impact,
+      ref_slot,
+      ref_reserve_s,
+      ref_reserve_a,
+      ref_reserve_b,
+      max_outflow,
+      outflow_window,
+      window_start,
+      window_reserve_s,
+      window_reserve_a,
+      window_reserve_b,
+      outflow_s,
+      outflow_a,
+      outflow_b,
+      is_emergency,
+      burn,
+      split,
+      reserve_precision,
+      volatility_ts,
+    ) = array_refs![
+      src, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8, 1, 8, 8, 8, 8, 1, 8, 8, 8, 8, 8, 8, 8,
+      8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 1, 32, 32, 1, 8
+    ];
+    // Pools are zeroed until initialized, any other version is unknown to this program
+    let state =
+      PoolState::try_from_primitive(state[0]).or(Err(ProgramError::InvalidAccountData))?;
+    if version[0] != POOL_VERSION && (version[0] != 0 || state != PoolState::Uninitialized) {
+      return Err(ProgramError::InvalidAccountData);
+    }
+    let is_permissioned = match is_permissioned {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let is_emergency = match is_emergency {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
     Ok(Pool {
       owner: Pubkey::new_from_array(*owner),
-      state: PoolState::try_from_primitive(state[0]).or(Err(ProgramError::InvalidAccountData))?,
+      state,
       mint_lpt: Pubkey::new_from_array(*mint_lpt),
       vault: Pubkey::new_from_array(*vault),
       mint_s: Pubkey::new_from_array(*mint_s),
@@ -124,12 +264,41 @@ impl Pack for Pool {
       mint_b: Pubkey::new_from_array(*mint_b),
       treasury_b: Pubkey::new_from_array(*treasury_b),
       reserve_b: u64::from_le_bytes(*reserve_b),
+      version: version[0],
+      fee_min: u64::from_le_bytes(*fee_min),
+      fee_max: u64::from_le_bytes(*fee_max),
+      volatility: u64::from_le_bytes(*volatility),
+      referral: u64::from_le_bytes(*referral),
+      is_permissioned,
+      max_reserve_s: u64::from_le_bytes(*max_reserve_s),
+      max_reserve_a: u64::from_le_bytes(*max_reserve_a),
+      max_reserve_b: u64::from_le_bytes(*max_reserve_b),
+      max_trade: u64::from_le_bytes(*max_trade),
+      max_impact: u64::from_le_bytes(*max_impact),
+      ref_slot: u64::from_le_bytes(*ref_slot),
+      ref_reserve_s: u64::from_le_bytes(*ref_reserve_s),
+      ref_reserve_a: u64::from_le_bytes(*ref_reserve_a),
+      ref_reserve_b: u64::from_le_bytes(*ref_reserve_b),
+      max_outflow: u64::from_le_bytes(*max_outflow),
+      outflow_window: u64::from_le_bytes(*outflow_window),
+      window_start: i64::from_le_bytes(*window_start),
+      window_reserve_s: u64::from_le_bytes(*window_reserve_s),
+      window_reserve_a: u64::from_le_bytes(*window_reserve_a),
+      window_reserve_b: u64::from_le_bytes(*window_reserve_b),
+      outflow_s: u64::from_le_bytes(*outflow_s),
+      outflow_a: u64::from_le_bytes(*outflow_a),
+      outflow_b: u64::from_le_bytes(*outflow_b),
+      is_emergency,
+      burn: Pubkey::new_from_array(*burn),
+      split: Pubkey::new_from_array(*split),
+      reserve_precision: reserve_precision[0],
+      volatility_ts: i64::from_le_bytes(*volatility_ts),
     })
   }
   // Pack data from the data struct to [u8]
   fn pack_into_slice(&self, dst: &mut [u8]) {
     msg!("Write pool data");
-    let dst = array_mut_ref![dst, 0, 313];
+    let dst = array_mut_ref![dst, 0, 565];
     let (
       dst_owner,
       dst_state,
@@ -144,7 +313,39 @@ impl Pack for Pool {
       dst_mint_b,
       dst_treasury_b,
       dst_reserve_b,
-    ) = mut_array_refs![dst, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8];
+      dst_version,
+      dst_fee_min,
+      dst_fee_max,
+      dst_volatility,
+      dst_referral,
+      dst_is_permissioned,
+      dst_max_reserve_s,
+      dst_max_reserve_a,
+      dst_max_reserve_b,
+      dst_max_trade,
+      dst_max_impact,
+      dst_ref_slot,
+      dst_ref_reserve_s,
+      dst_ref_reserve_a,
+      dst_ref_reserve_b,
+      dst_max_outflow,
+      dst_outflow_window,
+      dst_window_start,
+      dst_window_reserve_s,
+      dst_window_reserve_a,
+      dst_window_reserve_b,
+      dst_outflow_s,
+      dst_outflow_a,
+      dst_outflow_b,
+      dst_is_emergency,
+      dst_burn,
+      dst_split,
+      dst_reserve_precision,
+      dst_volatility_ts,
+    ) = mut_array_refs![
+      dst, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8, 1, 8, 8, 8, 8, 1, 8, 8, 8, 8, 8, 8, 8,
+      8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 1, 32, 32, 1, 8
+    ];
     let &Pool {
       ref owner,
       state,
@@ -159,6 +360,35 @@ impl Pack for Pool {
       ref mint_b,
       ref treasury_b,
       reserve_b,
+      version,
+      fee_min,
+      fee_max,
+      volatility,
+      referral,
+      is_permissioned,
+      max_reserve_s,
+      max_reserve_a,
+      max_reserve_b,
+      max_trade,
+      max_impact,
+      ref_slot,
+      ref_reserve_s,
+      ref_reserve_a,
+      ref_reserve_b,
+      max_outflow,
+      outflow_window,
+      window_start,
+      window_reserve_s,
+      window_reserve_a,
+      window_reserve_b,
+      outflow_s,
+      outflow_a,
+      outflow_b,
+      is_emergency,
+      ref burn,
+      ref split,
+      reserve_precision,
+      volatility_ts,
     } = self;
     dst_owner.copy_from_slice(owner.as_ref());
     *dst_state = [state as u8];
@@ -173,5 +403,34 @@ impl Pack for Pool {
     dst_mint_b.copy_from_slice(mint_b.as_ref());
     dst_treasury_b.copy_from_slice(treasury_b.as_ref());
     *dst_reserve_b = reserve_b.to_le_bytes();
+    *dst_version = [version];
+    *dst_fee_min = fee_min.to_le_bytes();
+    *dst_fee_max = fee_max.to_le_bytes();
+    *dst_volatility = volatility.to_le_bytes();
+    *dst_referral = referral.to_le_bytes();
+    *dst_is_permissioned = [is_permissioned as u8];
+    *dst_max_reserve_s = max_reserve_s.to_le_bytes();
+    *dst_max_reserve_a = max_reserve_a.to_le_bytes();
+    *dst_max_reserve_b = max_reserve_b.to_le_bytes();
+    *dst_max_trade = max_trade.to_le_bytes();
+    *dst_max_impact = max_impact.to_le_bytes();
+    *dst_ref_slot = ref_slot.to_le_bytes();
+    *dst_ref_reserve_s = ref_reserve_s.to_le_bytes();
+    *dst_ref_reserve_a = ref_reserve_a.to_le_bytes();
+    *dst_ref_reserve_b = ref_reserve_b.to_le_bytes();
+    *dst_max_outflow = max_outflow.to_le_bytes();
+    *dst_outflow_window = outflow_window.to_le_bytes();
+    *dst_window_start = window_start.to_le_bytes();
+    *dst_window_reserve_s = window_reserve_s.to_le_bytes();
+    *dst_window_reserve_a = window_reserve_a.to_le_bytes();
+    *dst_window_reserve_b = window_reserve_b.to_le_bytes();
+    *dst_outflow_s = outflow_s.to_le_bytes();
+    *dst_outflow_a = outflow_a.to_le_bytes();
+    *dst_outflow_b = outflow_b.to_le_bytes();
+    *dst_is_emergency = [is_emergency as u8];
+    dst_burn.copy_from_slice(burn.as_ref());
+    dst_split.copy_from_slice(split.as_ref());
+    *dst_reserve_precision = [reserve_precision];
+    *dst_volatility_ts = volatility_ts.to_le_bytes();
   }
 }
@@ -0,0 +1,101 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+pub const MAX_BENEFICIARIES: usize = 8;
+
+///
+/// Split share, the ratio of the vault paid to the beneficiary (10^9 is the whole vault)
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Share {
+  pub beneficiary: Pubkey,
+  pub ratio: u64,
+}
+
+///
+/// Split struct
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Split {
+  pub pool: Pubkey,
+  pub is_initialized: bool,
+  pub shares: [Share; MAX_BENEFICIARIES],
+}
+
+///
+/// Split implementation
+///
+impl Split {
+  // Shares in use
+  pub fn beneficiaries(&self) -> impl Iterator<Item = &Share> {
+    self.shares.iter().filter(|share| share.ratio != 0)
+  }
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Split {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Split {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Split {
+  // Fixed length
+  const LEN: usize = 32 + 1 + MAX_BENEFICIARIES * (32 + 8);
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read split data");
+    let src = array_ref![src, 0, 353];
+    let (pool, is_initialized, src_shares) = array_refs![src, 32, 1, 320];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let mut shares = [Share::default(); MAX_BENEFICIARIES];
+    for (share, src_share) in shares.iter_mut().zip(src_shares.chunks(40)) {
+      let (beneficiary, ratio) = array_refs![array_ref![src_share, 0, 40], 32, 8];
+      *share = Share {
+        beneficiary: Pubkey::new_from_array(*beneficiary),
+        ratio: u64::from_le_bytes(*ratio),
+      };
+    }
+    Ok(Split {
+      pool: Pubkey::new_from_array(*pool),
+      is_initialized,
+      shares,
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write split data");
+    let dst = array_mut_ref![dst, 0, 353];
+    let (dst_pool, dst_is_initialized, dst_shares) = mut_array_refs![dst, 32, 1, 320];
+    let &Split {
+      ref pool,
+      is_initialized,
+      ref shares,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+    for (share, dst_share) in shares.iter().zip(dst_shares.chunks_mut(40)) {
+      dst_share[..32].copy_from_slice(share.beneficiary.as_ref());
+      dst_share[32..].copy_from_slice(&share.ratio.to_le_bytes());
+    }
+  }
+}
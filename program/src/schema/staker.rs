@@ -0,0 +1,83 @@
+use crate::helper::math::{Decimal, U256};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+///
+/// Staker struct, the stake of an owner in a farm
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Staker {
+  pub farm: Pubkey,
+  pub owner: Pubkey,
+  pub is_initialized: bool,
+  pub amount: u64,
+  pub debt: Decimal,
+  pub reward: u64, // Accrued but not harvested yet
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Staker {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Staker {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Staker {
+  // Fixed length
+  const LEN: usize = 32 + 32 + 1 + 8 + 32 + 8;
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read staker data");
+    let src = array_ref![src, 0, 113];
+    let (farm, owner, is_initialized, amount, debt, reward) = array_refs![src, 32, 32, 1, 8, 32, 8];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    Ok(Staker {
+      farm: Pubkey::new_from_array(*farm),
+      owner: Pubkey::new_from_array(*owner),
+      is_initialized,
+      amount: u64::from_le_bytes(*amount),
+      debt: Decimal(U256::from_little_endian(debt)),
+      reward: u64::from_le_bytes(*reward),
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write staker data");
+    let dst = array_mut_ref![dst, 0, 113];
+    let (dst_farm, dst_owner, dst_is_initialized, dst_amount, dst_debt, dst_reward) =
+      mut_array_refs![dst, 32, 32, 1, 8, 32, 8];
+    let &Staker {
+      ref farm,
+      ref owner,
+      is_initialized,
+      amount,
+      debt,
+      reward,
+    } = self;
+    dst_farm.copy_from_slice(farm.as_ref());
+    dst_owner.copy_from_slice(owner.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+    *dst_amount = amount.to_le_bytes();
+    debt.0.to_little_endian(dst_debt);
+    *dst_reward = reward.to_le_bytes();
+  }
+}
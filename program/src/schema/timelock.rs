@@ -0,0 +1,319 @@
+use crate::schema::split::{Share, MAX_BENEFICIARIES};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_enum::TryFromPrimitive;
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+pub const TIMELOCK_DELAY: i64 = 172800; // 2 days
+pub const GRACE_PERIOD: i64 = 1209600; // 14 days
+
+///
+/// Timelock state
+///
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum TimelockState {
+  Uninitialized,
+  Queued,
+  Executed,
+  Cancelled,
+}
+// Not derived, num_enum would take a #[default] variant as the catch-all
+#[allow(clippy::derivable_impls)]
+impl Default for TimelockState {
+  fn default() -> Self {
+    TimelockState::Uninitialized
+  }
+}
+
+///
+/// Admin actions which must wait for the timelock
+///
+// Split shares are kept inline, the timelock stores the largest payload anyway
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Action {
+  #[default]
+  None,
+  TransferPoolOwnership {
+    new_owner: Pubkey,
+  },
+  UpdateFees {
+    fee_min: u64,
+    fee_max: u64,
+  },
+  SetDiscount {
+    trader: Pubkey,
+    discount: u64,
+  },
+  UpdateReferral {
+    referral: u64,
+  },
+  SetSplit {
+    shares: [Share; MAX_BENEFICIARIES],
+  },
+  UpdateRewardRate {
+    reward_rate: u64,
+  },
+  UpdatePermission {
+    is_permissioned: bool,
+  },
+  UpdateCaps {
+    max_reserve_s: u64,
+    max_reserve_a: u64,
+    max_reserve_b: u64,
+    max_trade: u64,
+  },
+  UpdateCircuitBreaker {
+    max_impact: u64,
+  },
+  UpdateRateLimit {
+    max_outflow: u64,
+    outflow_window: u64,
+  },
+}
+
+///
+/// Action implementation
+///
+impl Action {
+  // Length of the largest payload
+  pub const PAYLOAD_LEN: usize = MAX_BENEFICIARIES * (32 + 8);
+  // Fixed length (tag + the largest payload)
+  pub const LEN: usize = 1 + Self::PAYLOAD_LEN;
+  // Unpack data from [u8] to the action
+  pub fn unpack(src: &[u8; Action::LEN]) -> Result<Self, ProgramError> {
+    let (tag, rest) = array_refs![src, 1, Action::PAYLOAD_LEN];
+    Ok(match tag[0] {
+      0 => Action::None,
+      1 => {
+        let new_owner = array_ref![rest, 0, 32];
+        Action::TransferPoolOwnership {
+          new_owner: Pubkey::new_from_array(*new_owner),
+        }
+      }
+      2 => {
+        let (fee_min, fee_max) = array_refs![array_ref![rest, 0, 16], 8, 8];
+        Action::UpdateFees {
+          fee_min: u64::from_le_bytes(*fee_min),
+          fee_max: u64::from_le_bytes(*fee_max),
+        }
+      }
+      3 => {
+        let (trader, discount) = array_refs![array_ref![rest, 0, 40], 32, 8];
+        Action::SetDiscount {
+          trader: Pubkey::new_from_array(*trader),
+          discount: u64::from_le_bytes(*discount),
+        }
+      }
+      4 => {
+        let referral = array_ref![rest, 0, 8];
+        Action::UpdateReferral {
+          referral: u64::from_le_bytes(*referral),
+        }
+      }
+      5 => {
+        let mut shares = [Share::default(); MAX_BENEFICIARIES];
+        for (share, src_share) in shares.iter_mut().zip(rest.chunks(40)) {
+          let (beneficiary, ratio) = array_refs![array_ref![src_share, 0, 40], 32, 8];
+          *share = Share {
+            beneficiary: Pubkey::new_from_array(*beneficiary),
+            ratio: u64::from_le_bytes(*ratio),
+          };
+        }
+        Action::SetSplit { shares }
+      }
+      6 => {
+        let reward_rate = array_ref![rest, 0, 8];
+        Action::UpdateRewardRate {
+          reward_rate: u64::from_le_bytes(*reward_rate),
+        }
+      }
+      7 => {
+        let is_permissioned = match rest[0] {
+          0 => false,
+          1 => true,
+          _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Action::UpdatePermission { is_permissioned }
+      }
+      8 => {
+        let (max_reserve_s, max_reserve_a, max_reserve_b, max_trade) =
+          array_refs![array_ref![rest, 0, 32], 8, 8, 8, 8];
+        Action::UpdateCaps {
+          max_reserve_s: u64::from_le_bytes(*max_reserve_s),
+          max_reserve_a: u64::from_le_bytes(*max_reserve_a),
+          max_reserve_b: u64::from_le_bytes(*max_reserve_b),
+          max_trade: u64::from_le_bytes(*max_trade),
+        }
+      }
+      9 => {
+        let max_impact = array_ref![rest, 0, 8];
+        Action::UpdateCircuitBreaker {
+          max_impact: u64::from_le_bytes(*max_impact),
+        }
+      }
+      10 => {
+        let (max_outflow, outflow_window) = array_refs![array_ref![rest, 0, 16], 8, 8];
+        Action::UpdateRateLimit {
+          max_outflow: u64::from_le_bytes(*max_outflow),
+          outflow_window: u64::from_le_bytes(*outflow_window),
+        }
+      }
+      _ => return Err(ProgramError::InvalidAccountData),
+    })
+  }
+  // Pack data from the action to [u8]
+  pub fn pack(&self, dst: &mut [u8; Action::LEN]) {
+    *dst = [0; Action::LEN];
+    match self {
+      Action::None => dst[0] = 0,
+      Action::TransferPoolOwnership { new_owner } => {
+        dst[0] = 1;
+        dst[1..33].copy_from_slice(new_owner.as_ref());
+      }
+      Action::UpdateFees { fee_min, fee_max } => {
+        dst[0] = 2;
+        dst[1..9].copy_from_slice(&fee_min.to_le_bytes());
+        dst[9..17].copy_from_slice(&fee_max.to_le_bytes());
+      }
+      Action::SetDiscount { trader, discount } => {
+        dst[0] = 3;
+        dst[1..33].copy_from_slice(trader.as_ref());
+        dst[33..41].copy_from_slice(&discount.to_le_bytes());
+      }
+      Action::UpdateReferral { referral } => {
+        dst[0] = 4;
+        dst[1..9].copy_from_slice(&referral.to_le_bytes());
+      }
+      Action::SetSplit { shares } => {
+        dst[0] = 5;
+        for (share, dst_share) in shares.iter().zip(dst[1..].chunks_mut(40)) {
+          dst_share[..32].copy_from_slice(share.beneficiary.as_ref());
+          dst_share[32..].copy_from_slice(&share.ratio.to_le_bytes());
+        }
+      }
+      Action::UpdateRewardRate { reward_rate } => {
+        dst[0] = 6;
+        dst[1..9].copy_from_slice(&reward_rate.to_le_bytes());
+      }
+      Action::UpdatePermission { is_permissioned } => {
+        dst[0] = 7;
+        dst[1] = *is_permissioned as u8;
+      }
+      Action::UpdateCaps {
+        max_reserve_s,
+        max_reserve_a,
+        max_reserve_b,
+        max_trade,
+      } => {
+        dst[0] = 8;
+        dst[1..9].copy_from_slice(&max_reserve_s.to_le_bytes());
+        dst[9..17].copy_from_slice(&max_reserve_a.to_le_bytes());
+        dst[17..25].copy_from_slice(&max_reserve_b.to_le_bytes());
+        dst[25..33].copy_from_slice(&max_trade.to_le_bytes());
+      }
+      Action::UpdateCircuitBreaker { max_impact } => {
+        dst[0] = 9;
+        dst[1..9].copy_from_slice(&max_impact.to_le_bytes());
+      }
+      Action::UpdateRateLimit {
+        max_outflow,
+        outflow_window,
+      } => {
+        dst[0] = 10;
+        dst[1..9].copy_from_slice(&max_outflow.to_le_bytes());
+        dst[9..17].copy_from_slice(&outflow_window.to_le_bytes());
+      }
+    }
+  }
+}
+
+///
+/// Timelock struct
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timelock {
+  pub pool: Pubkey,
+  pub target: Pubkey,
+  pub state: TimelockState,
+  pub eta: i64,
+  pub action: Action,
+}
+
+///
+/// Timelock implementation
+///
+impl Timelock {
+  // Is queued
+  pub fn is_queued(&self) -> bool {
+    self.state == TimelockState::Queued
+  }
+  // Is ready to be executed at the given unix timestamp
+  pub fn is_ready(&self, now: i64) -> bool {
+    self.is_queued() && now >= self.eta
+  }
+  // Is no longer executable at the given unix timestamp
+  pub fn is_expired(&self, now: i64) -> bool {
+    now > self.eta.saturating_add(GRACE_PERIOD)
+  }
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Timelock {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Timelock {
+  fn is_initialized(&self) -> bool {
+    self.state != TimelockState::Uninitialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Timelock {
+  // Fixed length
+  const LEN: usize = 32 + 32 + 1 + 8 + Action::LEN;
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read timelock data");
+    let src = array_ref![src, 0, Timelock::LEN];
+    let (pool, target, state, eta, action) = array_refs![src, 32, 32, 1, 8, Action::LEN];
+    Ok(Timelock {
+      pool: Pubkey::new_from_array(*pool),
+      target: Pubkey::new_from_array(*target),
+      state: TimelockState::try_from_primitive(state[0])
+        .or(Err(ProgramError::InvalidAccountData))?,
+      eta: i64::from_le_bytes(*eta),
+      action: Action::unpack(action)?,
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write timelock data");
+    let dst = array_mut_ref![dst, 0, Timelock::LEN];
+    let (dst_pool, dst_target, dst_state, dst_eta, dst_action) =
+      mut_array_refs![dst, 32, 32, 1, 8, Action::LEN];
+    let &Timelock {
+      ref pool,
+      ref target,
+      state,
+      eta,
+      ref action,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    dst_target.copy_from_slice(target.as_ref());
+    *dst_state = [state as u8];
+    *dst_eta = eta.to_le_bytes();
+    action.pack(dst_action);
+  }
+}
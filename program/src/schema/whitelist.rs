@@ -0,0 +1,132 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+  msg,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack, Sealed},
+  pubkey::Pubkey,
+};
+
+pub const MAX_WHITELIST: usize = 32;
+
+///
+/// Whitelist entry, the discount is the part of the fee waived (10^9 is fee-exempt)
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Entry {
+  pub trader: Pubkey,
+  pub discount: u64,
+}
+
+///
+/// Whitelist struct
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Whitelist {
+  pub pool: Pubkey,
+  pub is_initialized: bool,
+  pub entries: [Entry; MAX_WHITELIST],
+}
+
+///
+/// Whitelist implementation
+///
+impl Whitelist {
+  // Discount of the trader, zero if not listed
+  pub fn get_discount(&self, trader: &Pubkey) -> u64 {
+    self
+      .entries
+      .iter()
+      .find(|entry| entry.discount != 0 && entry.trader == *trader)
+      .map(|entry| entry.discount)
+      .unwrap_or(0)
+  }
+  // Insert, update or remove (zero discount) the trader
+  // Return false if the whitelist is full
+  pub fn set_discount(&mut self, trader: &Pubkey, discount: u64) -> bool {
+    if let Some(entry) = self
+      .entries
+      .iter_mut()
+      .find(|entry| entry.discount != 0 && entry.trader == *trader)
+    {
+      *entry = Entry {
+        trader: *trader,
+        discount,
+      };
+      return true;
+    }
+    if discount == 0 {
+      return true;
+    }
+    if let Some(entry) = self.entries.iter_mut().find(|entry| entry.discount == 0) {
+      *entry = Entry {
+        trader: *trader,
+        discount,
+      };
+      return true;
+    }
+    false
+  }
+}
+
+///
+/// Sealed trait
+///
+impl Sealed for Whitelist {}
+
+///
+/// IsInitialized trait
+///
+impl IsInitialized for Whitelist {
+  fn is_initialized(&self) -> bool {
+    self.is_initialized
+  }
+}
+
+///
+/// Pack trait
+///
+impl Pack for Whitelist {
+  // Fixed length
+  const LEN: usize = 32 + 1 + MAX_WHITELIST * (32 + 8);
+  // Unpack data from [u8] to the data struct
+  fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+    msg!("Read whitelist data");
+    let src = array_ref![src, 0, 1313];
+    let (pool, is_initialized, src_entries) = array_refs![src, 32, 1, 1280];
+    let is_initialized = match is_initialized {
+      [0] => false,
+      [1] => true,
+      _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let mut entries = [Entry::default(); MAX_WHITELIST];
+    for (entry, src_entry) in entries.iter_mut().zip(src_entries.chunks(40)) {
+      let (trader, discount) = array_refs![array_ref![src_entry, 0, 40], 32, 8];
+      *entry = Entry {
+        trader: Pubkey::new_from_array(*trader),
+        discount: u64::from_le_bytes(*discount),
+      };
+    }
+    Ok(Whitelist {
+      pool: Pubkey::new_from_array(*pool),
+      is_initialized,
+      entries,
+    })
+  }
+  // Pack data from the data struct to [u8]
+  fn pack_into_slice(&self, dst: &mut [u8]) {
+    msg!("Write whitelist data");
+    let dst = array_mut_ref![dst, 0, 1313];
+    let (dst_pool, dst_is_initialized, dst_entries) = mut_array_refs![dst, 32, 1, 1280];
+    let &Whitelist {
+      ref pool,
+      is_initialized,
+      ref entries,
+    } = self;
+    dst_pool.copy_from_slice(pool.as_ref());
+    *dst_is_initialized = [is_initialized as u8];
+    for (entry, dst_entry) in entries.iter().zip(dst_entries.chunks_mut(40)) {
+      dst_entry[..32].copy_from_slice(entry.trader.as_ref());
+      dst_entry[32..].copy_from_slice(&entry.discount.to_le_bytes());
+    }
+  }
+}
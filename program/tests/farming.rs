@@ -0,0 +1,74 @@
+use main::helper::{farming::Farming, math::Decimal};
+use proptest::prelude::*;
+
+const MAX_STAKE: u64 = 1000000000000000000;
+const MAX_RATE: u64 = 100000000000; // The emission of MAX_ELAPSED fits in u64
+const MAX_ELAPSED: u64 = 100000000; // More than 3 years
+
+proptest! {
+  #[test]
+  fn nothing_accrues_without_stake(
+    reward_rate in 0..MAX_RATE,
+    elapsed in 0..MAX_ELAPSED,
+  ) {
+    let reward_per_share = Farming::reward_per_share(Decimal::zero(), reward_rate, elapsed, 0);
+    prop_assert_eq!(reward_per_share, Some(Decimal::zero()));
+  }
+
+  #[test]
+  fn stakers_never_share_more_than_the_emission(
+    reward_rate in 0..MAX_RATE,
+    elapsed in 0..MAX_ELAPSED,
+    stake_a in 1..MAX_STAKE,
+    stake_b in 1..MAX_STAKE,
+  ) {
+    let total_stake = stake_a + stake_b;
+    let debt_a = Farming::debt(stake_a, Decimal::zero()).unwrap();
+    let debt_b = Farming::debt(stake_b, Decimal::zero()).unwrap();
+    let reward_per_share =
+      Farming::reward_per_share(Decimal::zero(), reward_rate, elapsed, total_stake).unwrap();
+    let reward_a = Farming::pending(stake_a, reward_per_share, debt_a).unwrap();
+    let reward_b = Farming::pending(stake_b, reward_per_share, debt_b).unwrap();
+    let emission = reward_rate as u128 * elapsed as u128;
+    prop_assert!(reward_a as u128 + reward_b as u128 <= emission);
+    // Each staker gets its share of the emission, less the rounding
+    let share_a = emission * stake_a as u128 / total_stake as u128;
+    prop_assert!(reward_a as u128 <= share_a);
+    prop_assert!(reward_a as u128 + 1 >= share_a);
+  }
+
+  #[test]
+  fn accruing_in_steps_never_pays_more(
+    reward_rate in 0..MAX_RATE,
+    elapsed in 0..MAX_ELAPSED,
+    split in 0..MAX_ELAPSED,
+    stake in 1..MAX_STAKE,
+    total_extra in 0..MAX_STAKE,
+  ) {
+    let total_stake = stake + total_extra;
+    let first = split % (elapsed + 1);
+    let once =
+      Farming::reward_per_share(Decimal::zero(), reward_rate, elapsed, total_stake).unwrap();
+    let reward_once = Farming::pending(stake, once, Decimal::zero()).unwrap();
+    // Harvest in the middle, then at the end
+    let middle =
+      Farming::reward_per_share(Decimal::zero(), reward_rate, first, total_stake).unwrap();
+    let reward_first = Farming::pending(stake, middle, Decimal::zero()).unwrap();
+    let debt = Farming::debt(stake, middle).unwrap();
+    let end =
+      Farming::reward_per_share(middle, reward_rate, elapsed - first, total_stake).unwrap();
+    let reward_second = Farming::pending(stake, end, debt).unwrap();
+    prop_assert!(reward_first as u128 + reward_second as u128 <= reward_once as u128 + 1);
+    prop_assert!(end <= once);
+  }
+
+  #[test]
+  fn pending_is_zero_right_after_the_debt(
+    reward_per_share in any::<u64>(),
+    stake in 0..MAX_STAKE,
+  ) {
+    let reward_per_share = Decimal::from_u64(reward_per_share);
+    let debt = Farming::debt(stake, reward_per_share).unwrap();
+    prop_assert_eq!(Farming::pending(stake, reward_per_share, debt), Some(0));
+  }
+}
@@ -0,0 +1,73 @@
+use main::helper::math::{Decimal, Roots, Rounding, U256, U512};
+use proptest::prelude::*;
+
+fn is_floor_sqrt(n: u128, r: u128) -> bool {
+  let lower = matches!(r.checked_mul(r), Some(square) if square <= n);
+  let upper = !matches!((r + 1).checked_mul(r + 1), Some(square) if square <= n);
+  lower && upper
+}
+
+fn is_floor_cbrt(n: u128, r: u128) -> bool {
+  let cube = |x: u128| x.checked_mul(x).and_then(|square| square.checked_mul(x));
+  let lower = matches!(cube(r), Some(cube) if cube <= n);
+  let upper = !matches!(cube(r + 1), Some(cube) if cube <= n);
+  lower && upper
+}
+
+#[test]
+fn roots_of_edge_values() {
+  for n in (0..1000u128).chain([u64::MAX as u128, u128::MAX - 1, u128::MAX].iter().copied()) {
+    assert!(is_floor_sqrt(n, n.sqrt()), "sqrt({})", n);
+    assert!(is_floor_cbrt(n, n.cbrt()), "cbrt({})", n);
+  }
+  for shift in 0..128 {
+    let n = 1u128 << shift;
+    for n in [n - 1, n, n + 1].iter().copied() {
+      assert!(is_floor_sqrt(n, n.sqrt()), "sqrt({})", n);
+      assert!(is_floor_cbrt(n, n.cbrt()), "cbrt({})", n);
+    }
+  }
+}
+
+proptest! {
+  #[test]
+  fn sqrt_is_floor(n in any::<u128>()) {
+    prop_assert!(is_floor_sqrt(n, n.sqrt()));
+  }
+
+  #[test]
+  fn cbrt_is_floor(n in any::<u128>()) {
+    prop_assert!(is_floor_cbrt(n, n.cbrt()));
+  }
+
+  #[test]
+  fn wide_roots_match_u128(n in any::<u128>()) {
+    prop_assert_eq!(U256::from(n).sqrt(), U256::from(n.sqrt()));
+    prop_assert_eq!(U256::from(n).cbrt(), U256::from(n.cbrt()));
+    prop_assert_eq!(U512::from(n).sqrt(), U512::from(n.sqrt()));
+    prop_assert_eq!(U512::from(n).cbrt(), U512::from(n.cbrt()));
+  }
+
+  #[test]
+  fn decimal_rounding_brackets_the_exact_value(a in any::<u64>(), b in 1..u64::MAX) {
+    let floor = Decimal::from_u64(a).checked_div(Decimal::from_u64(b), Rounding::Floor).unwrap();
+    let ceil = Decimal::from_u64(a).checked_div(Decimal::from_u64(b), Rounding::Ceil).unwrap();
+    prop_assert!(floor <= ceil);
+    prop_assert!(ceil.0 - floor.0 <= U256::one());
+    prop_assert_eq!(floor.to_u64(Rounding::Floor).unwrap(), a / b);
+    prop_assert_eq!(ceil.to_u64(Rounding::Ceil).unwrap(), (a / b) + (a % b != 0) as u64);
+  }
+
+  #[test]
+  fn decimal_roots_bracket_the_exact_value(a in any::<u64>()) {
+    let x = Decimal::from_u64(a);
+    let sqrt_floor = x.sqrt(Rounding::Floor).unwrap();
+    let sqrt_ceil = x.sqrt(Rounding::Ceil).unwrap();
+    prop_assert!(sqrt_floor.checked_mul(sqrt_floor, Rounding::Floor).unwrap() <= x);
+    prop_assert!(sqrt_ceil.checked_mul(sqrt_ceil, Rounding::Ceil).unwrap() >= x);
+    let cbrt_floor = x.cbrt(Rounding::Floor).unwrap();
+    let cbrt_ceil = x.cbrt(Rounding::Ceil).unwrap();
+    prop_assert!(cbrt_floor <= cbrt_ceil);
+    prop_assert_eq!(cbrt_floor.to_u64(Rounding::Floor).unwrap() as u128, (a as u128).cbrt());
+  }
+}
@@ -0,0 +1,232 @@
+use main::helper::oracle::{Oracle, DECIMALS, FEE, MAX_FEE, VOLATILITY_HALF_LIFE};
+use proptest::prelude::*;
+
+const MIN_RESERVE: u64 = 1000000;
+const MAX_RESERVE: u64 = 1000000000000000000;
+
+proptest! {
+  #[test]
+  fn curve_is_monotonic(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+    extra in 1..MAX_RESERVE,
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let new_ask_reserve = Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    prop_assert!(new_ask_reserve <= ask_reserve);
+    // Bidding more never leaves more in the ask reserve
+    let more_ask_reserve = Oracle::curve(new_bid_reserve + extra, bid_reserve, ask_reserve).unwrap();
+    prop_assert!(more_ask_reserve <= new_ask_reserve);
+  }
+
+  #[test]
+  fn curve_preserves_k(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let new_ask_reserve = Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    let k = bid_reserve as u128 * ask_reserve as u128;
+    // The smallest ask reserve keeping k
+    prop_assert!(new_bid_reserve as u128 * new_ask_reserve as u128 >= k);
+    prop_assert!((new_bid_reserve as u128) * ((new_ask_reserve - 1) as u128) < k);
+  }
+
+  #[test]
+  fn curve_in_fee_splits_the_raw_output(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+    is_exempted in any::<bool>(),
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let new_ask_reserve_without_fee =
+      Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    let raw_amount = ask_reserve - new_ask_reserve_without_fee;
+    if let Some((new_ask_reserve, paid_amount, earning)) =
+      Oracle::curve_in_fee(new_bid_reserve, bid_reserve, ask_reserve, FEE, 0, is_exempted)
+    {
+      let fee = new_ask_reserve - new_ask_reserve_without_fee;
+      prop_assert_eq!(fee + earning + paid_amount, raw_amount);
+      prop_assert!(!is_exempted || earning == 0);
+    } else {
+      // Only a dust output cannot cover the fee and earning
+      prop_assert!(raw_amount < 1000);
+    }
+  }
+
+  #[test]
+  fn rake_never_mints_more_than_a_proportional_deposit(
+    unit_s in 1000..MAX_RESERVE / 1000,
+    unit_a in 1000..MAX_RESERVE / 1000,
+    unit_b in 1000..MAX_RESERVE / 1000,
+    reserve_lpt in MIN_RESERVE..MAX_RESERVE,
+    ratio in 1u64..10000,
+  ) {
+    // Deposit exactly ratio / 1000 of every reserve
+    let (reserve_s, reserve_a, reserve_b) = (unit_s * 1000, unit_a * 1000, unit_b * 1000);
+    let (delta_s, delta_a, delta_b) = (unit_s * ratio, unit_a * ratio, unit_b * ratio);
+    let (lpt, _, _, _) = Oracle::rake(
+      delta_s, delta_a, delta_b, reserve_s, reserve_a, reserve_b, reserve_lpt,
+    ).unwrap();
+    prop_assert!(lpt as u128 * 1000 <= ratio as u128 * reserve_lpt as u128);
+  }
+
+  #[test]
+  fn rake_single_side_mints_less_than_a_proportional_deposit(
+    reserve_s in MIN_RESERVE..MAX_RESERVE,
+    reserve_a in MIN_RESERVE..MAX_RESERVE,
+    reserve_b in MIN_RESERVE..MAX_RESERVE,
+    reserve_lpt in MIN_RESERVE..MAX_RESERVE,
+    delta in 1..MAX_RESERVE,
+    side in 0u8..3,
+  ) {
+    let (delta_s, delta_a, delta_b, reserve) = match side {
+      0 => (delta, 0, 0, reserve_s),
+      1 => (0, delta, 0, reserve_a),
+      _ => (0, 0, delta, reserve_b),
+    };
+    // Minting more than u64 LPT is rejected rather than bounded
+    let raked = Oracle::rake(
+      delta_s, delta_a, delta_b, reserve_s, reserve_a, reserve_b, reserve_lpt,
+    );
+    prop_assume!(raked.is_some());
+    let (lpt, _, _, _) = raked.unwrap();
+    prop_assert!(lpt as u128 * reserve as u128 <= delta as u128 * reserve_lpt as u128);
+  }
+
+  #[test]
+  fn curve_in_fee_charges_more_with_a_higher_fee(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+    fee in 0..MAX_FEE,
+    extra in 0..MAX_FEE,
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let low = Oracle::curve_in_fee(new_bid_reserve, bid_reserve, ask_reserve, fee, 0, true);
+    let high =
+      Oracle::curve_in_fee(new_bid_reserve, bid_reserve, ask_reserve, fee + extra, 0, true);
+    if let (Some((_, low_paid, _)), Some((_, high_paid, _))) = (low, high) {
+      prop_assert!(high_paid <= low_paid);
+    }
+  }
+
+  #[test]
+  fn curve_in_fee_pays_more_with_a_discount(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+    discount in 0..=DECIMALS,
+    is_exempted in any::<bool>(),
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let full = Oracle::curve_in_fee(
+      new_bid_reserve, bid_reserve, ask_reserve, FEE, 0, is_exempted,
+    );
+    let discounted = Oracle::curve_in_fee(
+      new_bid_reserve, bid_reserve, ask_reserve, FEE, discount, is_exempted,
+    );
+    if let (Some((_, full_paid, full_earning)), Some((_, paid, earning))) = (full, discounted) {
+      prop_assert!(paid >= full_paid);
+      prop_assert!(earning <= full_earning);
+    }
+    // A fee-exempt trader gets the raw output
+    let new_ask_reserve = Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    let (_, paid, earning) = Oracle::curve_in_fee(
+      new_bid_reserve, bid_reserve, ask_reserve, FEE, DECIMALS, is_exempted,
+    ).unwrap();
+    prop_assert_eq!(paid, ask_reserve - new_ask_reserve);
+    prop_assert_eq!(earning, 0);
+  }
+
+  #[test]
+  fn portion_is_a_floored_part_of_the_amount(
+    amount in any::<u64>(),
+    ratio in 0..=DECIMALS,
+  ) {
+    let portion = Oracle::portion(amount, ratio).unwrap();
+    prop_assert!(portion <= amount);
+    prop_assert!(portion as u128 * DECIMALS as u128 <= amount as u128 * ratio as u128);
+    prop_assert_eq!(Oracle::portion(amount, DECIMALS), Some(amount));
+    prop_assert_eq!(Oracle::portion(amount, DECIMALS + 1), None);
+  }
+
+  #[test]
+  fn fee_stays_within_the_bounds(
+    volatility in any::<u64>(),
+    fee_min in 0..MAX_FEE,
+    spread in 0..MAX_FEE,
+  ) {
+    let fee_max = fee_min + spread;
+    let fee = Oracle::fee(volatility, fee_min, fee_max);
+    prop_assert!(fee >= fee_min && fee <= fee_max);
+    // A calm pool charges the minimum
+    prop_assert_eq!(Oracle::fee(0, fee_min, fee_max), fee_min);
+  }
+
+  #[test]
+  fn volatility_follows_the_price_moves(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE / 2,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    volatility in 0..DECIMALS,
+    amount in 1..MAX_RESERVE / 2,
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    let new_ask_reserve = Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    // A still price only decays the estimate
+    let still = Oracle::volatility(
+      volatility, bid_reserve, ask_reserve, bid_reserve, ask_reserve,
+    ).unwrap();
+    prop_assert!(still <= volatility);
+    // A move never lowers it below the still estimate, and bigger moves weigh more
+    let moved = Oracle::volatility(
+      volatility, bid_reserve, ask_reserve, new_bid_reserve, new_ask_reserve,
+    ).unwrap();
+    prop_assert!(moved >= still);
+    let bigger = Oracle::volatility(
+      volatility, bid_reserve, ask_reserve, new_bid_reserve + amount, new_ask_reserve,
+    ).unwrap();
+    prop_assert!(bigger >= moved);
+  }
+
+  #[test]
+  fn volatility_decays_with_the_idle_time(
+    volatility in any::<u64>(),
+    elapsed in 0..100 * VOLATILITY_HALF_LIFE,
+    extra in 0..VOLATILITY_HALF_LIFE,
+  ) {
+    let decayed = Oracle::decay_volatility(volatility, elapsed);
+    prop_assert!(decayed <= volatility);
+    prop_assert!(Oracle::decay_volatility(volatility, elapsed + extra) <= decayed);
+    // Each half-life halves it
+    prop_assert_eq!(Oracle::decay_volatility(volatility, VOLATILITY_HALF_LIFE), volatility / 2);
+    prop_assert_eq!(Oracle::decay_volatility(volatility, 0), volatility);
+    prop_assert_eq!(Oracle::decay_volatility(volatility, -elapsed), volatility);
+  }
+
+  #[test]
+  fn impact_grows_with_the_swap(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE / 2,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE / 4,
+  ) {
+    // Scaling both reserves keeps the price
+    prop_assert_eq!(
+      Oracle::impact(bid_reserve, ask_reserve, 2 * bid_reserve, 2 * ask_reserve),
+      Some(0)
+    );
+    let new_bid_reserve = bid_reserve + amount;
+    let new_ask_reserve = Oracle::curve(new_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    let impact =
+      Oracle::impact(bid_reserve, ask_reserve, new_bid_reserve, new_ask_reserve).unwrap();
+    prop_assert!(impact > 0);
+    let more_bid_reserve = new_bid_reserve + amount;
+    let more_ask_reserve = Oracle::curve(more_bid_reserve, bid_reserve, ask_reserve).unwrap();
+    let more_impact =
+      Oracle::impact(bid_reserve, ask_reserve, more_bid_reserve, more_ask_reserve).unwrap();
+    prop_assert!(more_impact >= impact);
+  }
+}
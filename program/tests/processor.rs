@@ -0,0 +1,3467 @@
+use arrayref::{array_ref, array_refs};
+use main::{
+  error::AppError,
+  event::AppEvent,
+  helper::{
+    oracle::{Oracle, DECIMALS, FEE, MAX_FEE, VOLATILITY_HALF_LIFE},
+    pubutil::Boolean,
+  },
+  instruction::{WITH_MEMBER, WITH_REFERRER, WITH_WHITELIST},
+  processor::Processor,
+  schema::{
+    farm::Farm,
+    lock::{Lock, LockSchedule},
+    member::Member,
+    pool::{
+      minimum_reserve, Pool, PoolState, LEGACY_LEN, MINIMUM_LIQUIDITY, POOL_VERSION,
+      RESERVE_PRECISION,
+    },
+    split::Split,
+    staker::Staker,
+    timelock::{Action, Timelock, TimelockState, GRACE_PERIOD, TIMELOCK_DELAY},
+    whitelist::{Entry, Whitelist, MAX_WHITELIST},
+  },
+};
+use num_enum::TryFromPrimitive;
+use solana_program::{
+  clock::Clock,
+  instruction::{AccountMeta, Instruction, InstructionError},
+  program_pack::Pack,
+  pubkey::Pubkey,
+  system_instruction, system_program, sysvar,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use solana_sdk::{
+  account::AccountSharedData,
+  signature::{Keypair, Signer},
+  transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+  get_associated_token_address, instruction::create_associated_token_account,
+};
+use spl_token::state::{Account, Mint};
+use std::convert::TryInto;
+
+const RESERVE: u64 = 1000000000;
+const BALANCE: u64 = 1000000000000;
+
+///
+/// Local cluster with a pool and three funded token accounts
+///
+// Accounts of a farm
+struct Farming {
+  farm: Keypair,
+  treasury_lpt: Keypair,
+  treasury_reward: Keypair,
+}
+
+// Accounts of a liquidity lock
+struct Locking {
+  lock: Keypair,
+  escrow: Keypair,
+}
+
+// Accounts of a second pool
+struct Pooling {
+  pool: Keypair,
+  mint_lpt: Keypair,
+  treasurer: Pubkey,
+  treasuries: [Pubkey; 3],
+  lpt: Pubkey,
+}
+
+struct Env {
+  context: ProgramTestContext,
+  program_id: Pubkey,
+  stranger: Keypair,
+  pool: Keypair,
+  mint_lpt: Keypair,
+  vault: Keypair,
+  treasurer: Pubkey,
+  mints: [Pubkey; 3],
+  srcs: [Pubkey; 3],
+  treasuries: [Pubkey; 3],
+  lpt: Pubkey,
+  burn: Pubkey,
+}
+
+impl Env {
+  // Start a cluster with the mints, the funded accounts and an empty pool
+  async fn new() -> Self {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("main", program_id, processor!(Processor::process));
+    // Run the token programs natively, like the pool program itself
+    program_test.add_program(
+      "spl_token",
+      spl_token::id(),
+      processor!(spl_token::processor::Processor::process),
+    );
+    program_test.add_program(
+      "spl_associated_token_account",
+      spl_associated_token_account::id(),
+      processor!(spl_associated_token_account::processor::process_instruction),
+    );
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.pubkey();
+    let rent = context.banks_client.get_rent().await.unwrap();
+
+    let (pool, treasurer) = pool_keypair(&program_id);
+    let mint_lpt = Keypair::new();
+    let vault = Keypair::new();
+
+    let mut env = Env {
+      context,
+      program_id,
+      stranger: Keypair::new(),
+      lpt: get_associated_token_address(&payer, &mint_lpt.pubkey()),
+      burn: get_associated_token_address(&treasurer, &mint_lpt.pubkey()),
+      pool,
+      mint_lpt,
+      vault,
+      treasurer,
+      mints: [Pubkey::default(); 3],
+      srcs: [Pubkey::default(); 3],
+      treasuries: [Pubkey::default(); 3],
+    };
+
+    // Mints and funded accounts
+    for i in 0..3 {
+      let (mint, src) = env.create_mint(9).await;
+      env.mints[i] = mint;
+      env.srcs[i] = src;
+      env.treasuries[i] = get_associated_token_address(&treasurer, &mint);
+    }
+
+    // Pool, LPT mint and vault
+    let ixs = [
+      system_instruction::create_account(
+        &payer,
+        &env.pool.pubkey(),
+        rent.minimum_balance(Pool::LEN),
+        Pool::LEN as u64,
+        &program_id,
+      ),
+      system_instruction::create_account(
+        &payer,
+        &env.mint_lpt.pubkey(),
+        rent.minimum_balance(Mint::LEN),
+        Mint::LEN as u64,
+        &spl_token::id(),
+      ),
+      system_instruction::create_account(
+        &payer,
+        &env.vault.pubkey(),
+        rent.minimum_balance(Account::LEN),
+        Account::LEN as u64,
+        &spl_token::id(),
+      ),
+    ];
+    let signers = [clone(&env.pool), clone(&env.mint_lpt), clone(&env.vault)];
+    env
+      .execute(&ixs, &signers.iter().collect::<Vec<_>>())
+      .await
+      .unwrap();
+
+    env
+  }
+
+  // Create a mint and fund an account of the payer
+  async fn create_mint(&mut self, decimals: u8) -> (Pubkey, Pubkey) {
+    let payer = self.payer();
+    let rent = self.context.banks_client.get_rent().await.unwrap();
+    let mint = Keypair::new();
+    let src = get_associated_token_address(&payer, &mint.pubkey());
+    self
+      .execute(
+        &[
+          system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent.minimum_balance(Mint::LEN),
+            Mint::LEN as u64,
+            &spl_token::id(),
+          ),
+          spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &payer,
+            None,
+            decimals,
+          )
+          .unwrap(),
+          create_associated_token_account(&payer, &payer, &mint.pubkey(), &spl_token::id()),
+          spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &src,
+            &payer,
+            &[],
+            BALANCE,
+          )
+          .unwrap(),
+        ],
+        &[&mint],
+      )
+      .await
+      .unwrap();
+    (mint.pubkey(), src)
+  }
+
+  // Initialize a second pool of the given mints, funded by the given accounts
+  async fn another_pool(&mut self, mints: [Pubkey; 3], srcs: [Pubkey; 3]) -> Pooling {
+    let payer = self.payer();
+    let (pool, treasurer) = pool_keypair(&self.program_id);
+    let mint_lpt = Keypair::new();
+    let vault = Keypair::new();
+    self.create_account(&pool, Pool::LEN).await;
+    self.allocate(&mint_lpt, Mint::LEN, &spl_token::id()).await;
+    self.allocate(&vault, Account::LEN, &spl_token::id()).await;
+    let pooling = Pooling {
+      lpt: get_associated_token_address(&payer, &mint_lpt.pubkey()),
+      treasuries: mints.map(|mint| get_associated_token_address(&treasurer, &mint)),
+      pool,
+      mint_lpt,
+      treasurer,
+    };
+    let proof = self
+      .program_id
+      .xor(&(pooling.pool.pubkey().xor(&treasurer)));
+    let mut accounts = vec![
+      AccountMeta::new(payer, true),
+      AccountMeta::new_readonly(payer, false),
+      AccountMeta::new(pooling.pool.pubkey(), true),
+      AccountMeta::new(pooling.lpt, false),
+      AccountMeta::new(
+        get_associated_token_address(&treasurer, &pooling.mint_lpt.pubkey()),
+        false,
+      ),
+      AccountMeta::new(pooling.mint_lpt.pubkey(), false),
+      AccountMeta::new(vault.pubkey(), true),
+      AccountMeta::new_readonly(proof, false),
+    ];
+    for i in 0..3 {
+      accounts.push(AccountMeta::new(srcs[i], false));
+      accounts.push(AccountMeta::new_readonly(mints[i], false));
+      accounts.push(AccountMeta::new(pooling.treasuries[i], false));
+    }
+    accounts.extend(vec![
+      AccountMeta::new_readonly(treasurer, false),
+      AccountMeta::new_readonly(system_program::id(), false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+      AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+    ]);
+    let signers = [clone(&pooling.pool), vault];
+    self
+      .call(
+        pack(0, &[RESERVE, RESERVE, RESERVE]),
+        accounts,
+        &signers.iter().collect::<Vec<_>>(),
+      )
+      .await
+      .unwrap();
+    pooling
+  }
+
+  // Start a cluster with an initialized pool
+  async fn initialized() -> Self {
+    let mut env = Self::new().await;
+    env
+      .initialize_pool(RESERVE, RESERVE, RESERVE)
+      .await
+      .unwrap();
+    env
+  }
+
+  // Sign with the payer and the given signers
+  async fn execute(
+    &mut self,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+  ) -> Result<(), TransactionError> {
+    // A fresh blockhash keeps retried transactions apart
+    let blockhash = self
+      .context
+      .banks_client
+      .get_new_latest_blockhash(&self.context.last_blockhash)
+      .await
+      .unwrap();
+    self.context.last_blockhash = blockhash;
+    let mut keypairs = vec![&self.context.payer];
+    keypairs.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+      ixs,
+      Some(&self.context.payer.pubkey()),
+      &keypairs,
+      blockhash,
+    );
+    self
+      .context
+      .banks_client
+      .process_transaction(transaction)
+      .await
+      .map_err(|er| er.unwrap())
+  }
+
+  // Call the program with the given accounts
+  // Logs and return data of the instructions, run without committing them
+  async fn simulate(&mut self, ixs: &[Instruction]) -> (Vec<String>, Vec<u8>) {
+    let transaction = Transaction::new_signed_with_payer(
+      ixs,
+      Some(&self.context.payer.pubkey()),
+      &[&self.context.payer],
+      self.context.last_blockhash,
+    );
+    let simulation = self
+      .context
+      .banks_client
+      .simulate_transaction(transaction)
+      .await
+      .unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let details = simulation.simulation_details.unwrap();
+    let return_data = details
+      .return_data
+      .map(|return_data| return_data.data)
+      .unwrap_or_default();
+    (details.logs, return_data)
+  }
+
+  async fn call(
+    &mut self,
+    data: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+    signers: &[&Keypair],
+  ) -> Result<(), TransactionError> {
+    let ix = Instruction {
+      program_id: self.program_id,
+      accounts,
+      data,
+    };
+    self.execute(&[ix], signers).await
+  }
+
+  fn payer(&self) -> Pubkey {
+    self.context.payer.pubkey()
+  }
+
+  async fn pool_data(&mut self) -> Pool {
+    let pool = self.pool.pubkey();
+    self.pool_data_of(&pool).await
+  }
+
+  async fn pool_data_of(&mut self, pool: &Pubkey) -> Pool {
+    let account = self
+      .context
+      .banks_client
+      .get_account(*pool)
+      .await
+      .unwrap()
+      .unwrap();
+    read_pool(&account.data)
+  }
+
+  async fn timelock_data(&mut self, timelock: &Pubkey) -> Timelock {
+    let account = self
+      .context
+      .banks_client
+      .get_account(*timelock)
+      .await
+      .unwrap()
+      .unwrap();
+    read_timelock(&account.data)
+  }
+
+  async fn whitelist_data(&mut self, whitelist: &Pubkey) -> Whitelist {
+    let account = self
+      .context
+      .banks_client
+      .get_account(*whitelist)
+      .await
+      .unwrap()
+      .unwrap();
+    read_whitelist(&account.data)
+  }
+
+  async fn balance(&mut self, key: &Pubkey) -> u64 {
+    let account = self
+      .context
+      .banks_client
+      .get_account(*key)
+      .await
+      .unwrap()
+      .unwrap();
+    Account::unpack(&account.data).unwrap().amount
+  }
+
+  async fn supply(&mut self) -> u64 {
+    let mint_lpt = self.mint_lpt.pubkey();
+    self.supply_of(&mint_lpt).await
+  }
+
+  async fn supply_of(&mut self, mint_lpt: &Pubkey) -> u64 {
+    let account = self
+      .context
+      .banks_client
+      .get_account(*mint_lpt)
+      .await
+      .unwrap()
+      .unwrap();
+    Mint::unpack(&account.data).unwrap().supply
+  }
+
+  // Move tokens straight into a treasury, bypassing the program
+  async fn gift(&mut self, index: usize, amount: u64) {
+    let ix = spl_token::instruction::transfer(
+      &spl_token::id(),
+      &self.srcs[index],
+      &self.treasuries[index],
+      &self.payer(),
+      &[],
+      amount,
+    )
+    .unwrap();
+    self.execute(&[ix], &[]).await.unwrap();
+  }
+
+  // Move the cluster clock forward
+  async fn warp(&mut self, seconds: i64) {
+    let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    self.context.set_sysvar(&clock);
+  }
+
+  async fn now(&mut self) -> i64 {
+    let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp
+  }
+
+  // Move the cluster clock to the next slot
+  async fn next_slot(&mut self) {
+    let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+    clock.slot += 1;
+    self.context.set_sysvar(&clock);
+  }
+
+  // Move the cluster clock past the timelock
+  async fn skip_timelock(&mut self) {
+    self.warp(TIMELOCK_DELAY).await;
+  }
+
+  // Instructions
+
+  async fn initialize_pool(
+    &mut self,
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+  ) -> Result<(), TransactionError> {
+    self
+      .initialize_pool_with(reserve_s, reserve_a, reserve_b, None)
+      .await
+  }
+
+  // The precision byte is omitted for the default minimum reserve
+  async fn initialize_pool_with(
+    &mut self,
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    reserve_precision: Option<u8>,
+  ) -> Result<(), TransactionError> {
+    let proof = self
+      .program_id
+      .xor(&(self.pool.pubkey().xor(&self.treasurer)));
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new_readonly(self.payer(), false),
+      AccountMeta::new(self.pool.pubkey(), true),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.burn, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(self.vault.pubkey(), true),
+      AccountMeta::new_readonly(proof, false),
+    ];
+    for i in 0..3 {
+      accounts.push(AccountMeta::new(self.srcs[i], false));
+      accounts.push(AccountMeta::new_readonly(self.mints[i], false));
+      accounts.push(AccountMeta::new(self.treasuries[i], false));
+    }
+    accounts.extend(vec![
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(system_program::id(), false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+      AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+    ]);
+    let signers = [clone(&self.pool), clone(&self.vault)];
+    let mut data = pack(0, &[reserve_s, reserve_a, reserve_b]);
+    data.extend(reserve_precision);
+    self
+      .call(data, accounts, &signers.iter().collect::<Vec<_>>())
+      .await
+  }
+
+  async fn add_liquidity(
+    &mut self,
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+    treasuries: [Pubkey; 3],
+  ) -> Result<(), TransactionError> {
+    self
+      .add_liquidity_with(delta_s, delta_a, delta_b, treasuries, &[])
+      .await
+  }
+
+  // Add liquidity with the optional allowlist entry
+  async fn add_liquidity_with(
+    &mut self,
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+    treasuries: [Pubkey; 3],
+    optional: &[Pubkey],
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+    ];
+    for (src, treasury) in self.srcs.iter().zip(treasuries.iter()) {
+      accounts.push(AccountMeta::new(*src, false));
+      accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    for key in optional {
+      accounts.push(AccountMeta::new_readonly(*key, false));
+    }
+    self
+      .call(pack(1, &[delta_s, delta_a, delta_b]), accounts, &[])
+      .await
+  }
+
+  // Deposit a single token
+  async fn zap_in(
+    &mut self,
+    index: usize,
+    amount: u64,
+    min_lpt: u64,
+    treasury: Pubkey,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(self.srcs[index], false),
+      AccountMeta::new(treasury, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    let mut data = vec![35, index as u8];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&min_lpt.to_le_bytes());
+    self.call(data, accounts, &[]).await
+  }
+
+  async fn remove_liquidity(
+    &mut self,
+    lpt: u64,
+    treasuries: [Pubkey; 3],
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+    ];
+    for (src, treasury) in self.srcs.iter().zip(treasuries.iter()) {
+      accounts.push(AccountMeta::new(*src, false));
+      accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    self.call(pack(2, &[lpt]), accounts, &[]).await
+  }
+
+  // Move LPT into another pool, refunding the tokens it does not hold
+  async fn migrate_liquidity(
+    &mut self,
+    lpt: u64,
+    min_lpt_out: u64,
+    to: &Pooling,
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+    ];
+    for treasury in self.treasuries.iter() {
+      accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.extend(vec![
+      AccountMeta::new(to.pool.pubkey(), false),
+      AccountMeta::new(to.lpt, false),
+      AccountMeta::new(to.mint_lpt.pubkey(), false),
+    ]);
+    for treasury in to.treasuries.iter() {
+      accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new_readonly(to.treasurer, false));
+    for src in self.srcs.iter() {
+      accounts.push(AccountMeta::new(*src, false));
+    }
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    self
+      .call(pack(36, &[lpt, min_lpt_out]), accounts, &[])
+      .await
+  }
+
+  async fn swap(
+    &mut self,
+    amount: u64,
+    limit: u64,
+    bid: usize,
+    ask: usize,
+    treasury_sen: Pubkey,
+  ) -> Result<(), TransactionError> {
+    self
+      .swap_with(amount, limit, bid, ask, treasury_sen, &[])
+      .await
+  }
+
+  // Swap with the optional whitelist, allowlist entry and referrer accounts
+  async fn swap_with(
+    &mut self,
+    amount: u64,
+    limit: u64,
+    bid: usize,
+    ask: usize,
+    treasury_sen: Pubkey,
+    optional: &[(u8, Pubkey)],
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.vault.pubkey(), false),
+      AccountMeta::new(self.srcs[bid], false),
+      AccountMeta::new(self.treasuries[bid], false),
+      AccountMeta::new(self.srcs[ask], false),
+      AccountMeta::new(self.treasuries[ask], false),
+      AccountMeta::new(treasury_sen, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    let mut flags = 0;
+    for &(flag, key) in optional {
+      flags |= flag;
+      accounts.push(AccountMeta::new(key, false));
+    }
+    let mut data = pack(3, &[amount, limit]);
+    data.push(flags);
+    self.call(data, accounts, &[]).await
+  }
+
+  // Run several swaps, each leg is (bid, ask, amount, limit)
+  async fn batch_swap(
+    &mut self,
+    legs: &[(usize, usize, u64, u64)],
+  ) -> Result<(), TransactionError> {
+    let ix = self.batch_swap_ix(legs);
+    self.execute(&[ix], &[]).await
+  }
+
+  fn batch_swap_ix(&self, legs: &[(usize, usize, u64, u64)]) -> Instruction {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.vault.pubkey(), false),
+      AccountMeta::new(self.treasuries[0], false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    let mut data = vec![37, legs.len() as u8];
+    for &(bid, ask, amount, limit) in legs.iter() {
+      accounts.push(AccountMeta::new(self.srcs[bid], false));
+      accounts.push(AccountMeta::new(self.treasuries[bid], false));
+      accounts.push(AccountMeta::new(self.srcs[ask], false));
+      accounts.push(AccountMeta::new(self.treasuries[ask], false));
+      data.extend_from_slice(&amount.to_le_bytes());
+      data.extend_from_slice(&limit.to_le_bytes());
+    }
+    Instruction {
+      program_id: self.program_id,
+      accounts,
+      data,
+    }
+  }
+
+  // Owner instruction, signed by the payer unless a stranger is given
+  async fn owner_call(
+    &mut self,
+    data: Vec<u8>,
+    mut accounts: Vec<AccountMeta>,
+    stranger: bool,
+    signers: &[&Keypair],
+  ) -> Result<(), TransactionError> {
+    let owner = if stranger {
+      self.stranger.pubkey()
+    } else {
+      self.payer()
+    };
+    accounts.insert(0, AccountMeta::new(owner, true));
+    let stranger_keypair = clone(&self.stranger);
+    let mut keypairs = signers.to_vec();
+    if stranger {
+      keypairs.push(&stranger_keypair);
+    }
+    self.call(data, accounts, &keypairs).await
+  }
+
+  async fn freeze_pool(&mut self, stranger: bool) -> Result<(), TransactionError> {
+    let accounts = vec![AccountMeta::new(self.pool.pubkey(), false)];
+    self.owner_call(pack(4, &[]), accounts, stranger, &[]).await
+  }
+
+  async fn thaw_pool(&mut self, stranger: bool) -> Result<(), TransactionError> {
+    let accounts = vec![AccountMeta::new(self.pool.pubkey(), false)];
+    self.owner_call(pack(5, &[]), accounts, stranger, &[]).await
+  }
+
+  async fn earn(&mut self, amount: u64, stranger: bool) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.vault.pubkey(), false),
+      AccountMeta::new(self.srcs[0], false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    self
+      .owner_call(pack(6, &[amount]), accounts, stranger, &[])
+      .await
+  }
+
+  // Allocate an account for the program
+  async fn create_account(&mut self, keypair: &Keypair, len: usize) {
+    let program_id = self.program_id;
+    self.allocate(keypair, len, &program_id).await;
+  }
+
+  // Allocate an account for the given owner program
+  async fn allocate(&mut self, keypair: &Keypair, len: usize, owner: &Pubkey) {
+    let rent = self.context.banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+      &self.payer(),
+      &keypair.pubkey(),
+      rent.minimum_balance(len),
+      len as u64,
+      owner,
+    );
+    if self
+      .context
+      .banks_client
+      .get_account(keypair.pubkey())
+      .await
+      .unwrap()
+      .is_none()
+    {
+      self.execute(&[create], &[keypair]).await.unwrap();
+    }
+  }
+
+  async fn create_timelock(&mut self, timelock: &Keypair) {
+    self.create_account(timelock, Timelock::LEN).await;
+  }
+
+  async fn transfer_pool_ownership(
+    &mut self,
+    new_owner: &Pubkey,
+    timelock: &Keypair,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    self.create_timelock(timelock).await;
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new_readonly(*new_owner, false),
+      AccountMeta::new(timelock.pubkey(), true),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self
+      .owner_call(pack(7, &[]), accounts, stranger, &[timelock])
+      .await
+  }
+
+  // Queue an owner action into a fresh timelock
+  async fn queue_call(
+    &mut self,
+    data: Vec<u8>,
+    mut accounts: Vec<AccountMeta>,
+    stranger: bool,
+    timelock: &Keypair,
+  ) -> Result<(), TransactionError> {
+    self.create_timelock(timelock).await;
+    accounts.push(AccountMeta::new(timelock.pubkey(), true));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    self.owner_call(data, accounts, stranger, &[timelock]).await
+  }
+
+  // Queue an owner action, then execute it on the target once the timelock has passed
+  async fn timelocked_call(
+    &mut self,
+    data: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+    stranger: bool,
+    target: &Pubkey,
+  ) -> Result<(), TransactionError> {
+    let timelock = Keypair::new();
+    self.queue_call(data, accounts, stranger, &timelock).await?;
+    self.skip_timelock().await;
+    self.execute_action_on(&timelock.pubkey(), target).await
+  }
+
+  async fn execute_action(&mut self, timelock: &Pubkey) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(*timelock, false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self.owner_call(pack(8, &[]), accounts, false, &[]).await
+  }
+
+  async fn execute_action_on(
+    &mut self,
+    timelock: &Pubkey,
+    target: &Pubkey,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(*timelock, false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+      AccountMeta::new(*target, false),
+    ];
+    self.owner_call(pack(8, &[]), accounts, false, &[]).await
+  }
+
+  async fn cancel_action(&mut self, timelock: &Pubkey) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(*timelock, false),
+    ];
+    self.owner_call(pack(9, &[]), accounts, false, &[]).await
+  }
+
+  async fn sync(&mut self, treasuries: [Pubkey; 3]) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new_readonly(treasuries[0], false),
+      AccountMeta::new_readonly(treasuries[1], false),
+      AccountMeta::new_readonly(treasuries[2], false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self.call(pack(10, &[]), accounts, &[]).await
+  }
+
+  async fn skim(&mut self, stranger: bool) -> Result<(), TransactionError> {
+    let mut accounts = vec![AccountMeta::new(self.pool.pubkey(), false)];
+    for i in 0..3 {
+      accounts.push(AccountMeta::new(self.treasuries[i], false));
+      accounts.push(AccountMeta::new(self.srcs[i], false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    self
+      .owner_call(pack(11, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  async fn donate(
+    &mut self,
+    delta_s: u64,
+    delta_a: u64,
+    delta_b: u64,
+    treasuries: [Pubkey; 3],
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new(self.pool.pubkey(), false),
+    ];
+    for (src, treasury) in self.srcs.iter().zip(treasuries.iter()) {
+      accounts.push(AccountMeta::new(*src, false));
+      accounts.push(AccountMeta::new(*treasury, false));
+    }
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    self
+      .call(pack(12, &[delta_s, delta_a, delta_b]), accounts, &[])
+      .await
+  }
+
+  // Rewrite the pool in the unversioned layout, as written before the later fields
+  async fn downgrade_pool(&mut self) {
+    let pool = self.pool.pubkey();
+    let account = self
+      .context
+      .banks_client
+      .get_account(pool)
+      .await
+      .unwrap()
+      .unwrap();
+    let rent = self.context.banks_client.get_rent().await.unwrap();
+    let mut legacy = AccountSharedData::new(
+      rent.minimum_balance(LEGACY_LEN),
+      LEGACY_LEN,
+      &self.program_id,
+    );
+    legacy.set_data(account.data[..LEGACY_LEN].to_vec());
+    self.context.set_account(&pool, &legacy);
+  }
+
+  // Migrate into the given burn account, a fresh one if not yet allocated
+  async fn migrate_pool(&mut self, burn: &Keypair, stranger: bool) -> Result<(), TransactionError> {
+    self.allocate(burn, Account::LEN, &spl_token::id()).await;
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(burn.pubkey(), false),
+      AccountMeta::new_readonly(self.mint_lpt.pubkey(), false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(system_program::id(), false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    self
+      .owner_call(pack(38, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  async fn close_pool(&mut self, stranger: bool) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(self.burn, false),
+      AccountMeta::new(self.vault.pubkey(), false),
+    ];
+    for i in 0..3 {
+      accounts.push(AccountMeta::new(self.treasuries[i], false));
+      accounts.push(AccountMeta::new(self.srcs[i], false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    self
+      .owner_call(pack(13, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  async fn reseed_pool(
+    &mut self,
+    reserve_s: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(self.burn, false),
+    ];
+    for i in 0..3 {
+      accounts.push(AccountMeta::new(self.srcs[i], false));
+      accounts.push(AccountMeta::new_readonly(self.mints[i], false));
+      accounts.push(AccountMeta::new(self.treasuries[i], false));
+    }
+    accounts.push(AccountMeta::new_readonly(self.treasurer, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    self
+      .owner_call(
+        pack(14, &[reserve_s, reserve_a, reserve_b]),
+        accounts,
+        false,
+        &[],
+      )
+      .await
+  }
+
+  async fn update_fees(
+    &mut self,
+    fee_min: u64,
+    fee_max: u64,
+    timelock: &Keypair,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    self.create_timelock(timelock).await;
+    let accounts = vec![
+      AccountMeta::new(self.pool.pubkey(), false),
+      AccountMeta::new(timelock.pubkey(), true),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self
+      .owner_call(
+        pack(15, &[fee_min, fee_max]),
+        accounts,
+        stranger,
+        &[timelock],
+      )
+      .await
+  }
+
+  async fn initialize_whitelist(
+    &mut self,
+    whitelist: &Keypair,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    self.create_account(whitelist, Whitelist::LEN).await;
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(whitelist.pubkey(), true),
+    ];
+    self
+      .owner_call(pack(16, &[]), accounts, stranger, &[whitelist])
+      .await
+  }
+
+  async fn set_discount(
+    &mut self,
+    whitelist: &Pubkey,
+    trader: &Pubkey,
+    discount: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new_readonly(*whitelist, false),
+      AccountMeta::new_readonly(*trader, false),
+    ];
+    self
+      .timelocked_call(pack(17, &[discount]), accounts, stranger, whitelist)
+      .await
+  }
+
+  async fn update_referral(
+    &mut self,
+    referral: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let pool = self.pool.pubkey();
+    let accounts = vec![AccountMeta::new(pool, false)];
+    self
+      .timelocked_call(pack(18, &[referral]), accounts, stranger, &pool)
+      .await
+  }
+
+  async fn update_permission(
+    &mut self,
+    is_permissioned: bool,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let pool = self.pool.pubkey();
+    let accounts = vec![AccountMeta::new(pool, false)];
+    let data = vec![28, is_permissioned as u8];
+    self.timelocked_call(data, accounts, stranger, &pool).await
+  }
+
+  // Allowlist entry of the member
+  fn member(&self, member: &Pubkey) -> Pubkey {
+    Member::find_address(&self.pool.pubkey(), member, &self.program_id).0
+  }
+
+  async fn add_member(&mut self, member: &Pubkey, stranger: bool) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new_readonly(*member, false),
+      AccountMeta::new(self.member(member), false),
+      AccountMeta::new_readonly(system_program::id(), false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    self
+      .owner_call(pack(29, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  async fn remove_member(
+    &mut self,
+    member: &Pubkey,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(self.member(member), false),
+    ];
+    self
+      .owner_call(pack(30, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  async fn update_caps(
+    &mut self,
+    max_reserves: [u64; 3],
+    max_trade: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let [max_reserve_s, max_reserve_a, max_reserve_b] = max_reserves;
+    let pool = self.pool.pubkey();
+    let accounts = vec![AccountMeta::new(pool, false)];
+    let data = pack(
+      31,
+      &[max_reserve_s, max_reserve_a, max_reserve_b, max_trade],
+    );
+    self.timelocked_call(data, accounts, stranger, &pool).await
+  }
+
+  async fn update_circuit_breaker(
+    &mut self,
+    max_impact: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let pool = self.pool.pubkey();
+    let accounts = vec![AccountMeta::new(pool, false)];
+    self
+      .timelocked_call(pack(32, &[max_impact]), accounts, stranger, &pool)
+      .await
+  }
+
+  async fn update_rate_limit(
+    &mut self,
+    max_outflow: u64,
+    outflow_window: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let pool = self.pool.pubkey();
+    let accounts = vec![AccountMeta::new(pool, false)];
+    self
+      .timelocked_call(
+        pack(33, &[max_outflow, outflow_window]),
+        accounts,
+        stranger,
+        &pool,
+      )
+      .await
+  }
+
+  async fn update_emergency(
+    &mut self,
+    is_emergency: bool,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![AccountMeta::new(self.pool.pubkey(), false)];
+    let data = vec![34, is_emergency as u8];
+    self.owner_call(data, accounts, stranger, &[]).await
+  }
+
+  // Open a token account of the given wallet
+  async fn open_account(&mut self, wallet: &Pubkey, index: usize) -> Pubkey {
+    let ix =
+      create_associated_token_account(&self.payer(), wallet, &self.mints[index], &spl_token::id());
+    self.execute(&[ix], &[]).await.unwrap();
+    get_associated_token_address(wallet, &self.mints[index])
+  }
+
+  async fn set_split(
+    &mut self,
+    split: &Keypair,
+    ratios: &[u64],
+    beneficiaries: &[Pubkey],
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let timelock = Keypair::new();
+    self
+      .queue_split(split, ratios, beneficiaries, stranger, &timelock)
+      .await?;
+    self.skip_timelock().await;
+    self
+      .execute_action_on(&timelock.pubkey(), &split.pubkey())
+      .await
+  }
+
+  async fn queue_split(
+    &mut self,
+    split: &Keypair,
+    ratios: &[u64],
+    beneficiaries: &[Pubkey],
+    stranger: bool,
+    timelock: &Keypair,
+  ) -> Result<(), TransactionError> {
+    self.create_account(split, Split::LEN).await;
+    self.create_timelock(timelock).await;
+    let mut accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(split.pubkey(), true),
+      AccountMeta::new(timelock.pubkey(), true),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    for beneficiary in beneficiaries {
+      accounts.push(AccountMeta::new_readonly(*beneficiary, false));
+    }
+    self
+      .owner_call(pack(19, ratios), accounts, stranger, &[split, timelock])
+      .await
+  }
+
+  async fn distribute_earnings(
+    &mut self,
+    split: &Pubkey,
+    beneficiaries: &[Pubkey],
+  ) -> Result<(), TransactionError> {
+    let mut accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(self.vault.pubkey(), false),
+      AccountMeta::new_readonly(*split, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for beneficiary in beneficiaries {
+      accounts.push(AccountMeta::new(*beneficiary, false));
+    }
+    self.call(pack(20, &[]), accounts, &[]).await
+  }
+
+  // Farm rewarding the SEN token
+  async fn initialize_farm(
+    &mut self,
+    reward_rate: u64,
+    stranger: bool,
+  ) -> Result<Farming, TransactionError> {
+    let farm = Farming {
+      farm: Keypair::new(),
+      treasury_lpt: Keypair::new(),
+      treasury_reward: Keypair::new(),
+    };
+    self.create_account(&farm.farm, Farm::LEN).await;
+    self
+      .allocate(&farm.treasury_lpt, Account::LEN, &spl_token::id())
+      .await;
+    self
+      .allocate(&farm.treasury_reward, Account::LEN, &spl_token::id())
+      .await;
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(farm.farm.pubkey(), true),
+      AccountMeta::new_readonly(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(farm.treasury_lpt.pubkey(), true),
+      AccountMeta::new_readonly(self.mints[0], false),
+      AccountMeta::new(farm.treasury_reward.pubkey(), true),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self
+      .owner_call(
+        pack(21, &[reward_rate]),
+        accounts,
+        stranger,
+        &[&farm.farm, &farm.treasury_lpt, &farm.treasury_reward],
+      )
+      .await
+      .map(|_| farm)
+  }
+
+  async fn update_reward_rate(
+    &mut self,
+    farm: &Farming,
+    reward_rate: u64,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new_readonly(farm.farm.pubkey(), false),
+    ];
+    self
+      .timelocked_call(
+        pack(22, &[reward_rate]),
+        accounts,
+        stranger,
+        &farm.farm.pubkey(),
+      )
+      .await
+  }
+
+  // Move reward tokens into the farm
+  async fn fund_farm(&mut self, farm: &Farming, amount: u64) {
+    let ix = spl_token::instruction::transfer(
+      &spl_token::id(),
+      &self.srcs[0],
+      &farm.treasury_reward.pubkey(),
+      &self.payer(),
+      &[],
+      amount,
+    )
+    .unwrap();
+    self.execute(&[ix], &[]).await.unwrap();
+  }
+
+  fn stake_ix(&self, farm: &Farming, staker: &Pubkey, amount: u64) -> Instruction {
+    Instruction {
+      program_id: self.program_id,
+      accounts: vec![
+        AccountMeta::new(self.payer(), true),
+        AccountMeta::new(farm.farm.pubkey(), false),
+        AccountMeta::new(*staker, true),
+        AccountMeta::new(self.lpt, false),
+        AccountMeta::new(farm.treasury_lpt.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+      ],
+      data: pack(23, &[amount]),
+    }
+  }
+
+  async fn stake(
+    &mut self,
+    farm: &Farming,
+    staker: &Keypair,
+    amount: u64,
+  ) -> Result<(), TransactionError> {
+    self.create_account(staker, Staker::LEN).await;
+    let ix = self.stake_ix(farm, &staker.pubkey(), amount);
+    self.execute(&[ix], &[staker]).await
+  }
+
+  async fn unstake(
+    &mut self,
+    farm: &Farming,
+    staker: &Pubkey,
+    amount: u64,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(farm.farm.pubkey(), false),
+      AccountMeta::new(*staker, false),
+      AccountMeta::new(farm.treasury_lpt.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self.call(pack(24, &[amount]), accounts, &[]).await
+  }
+
+  async fn harvest(
+    &mut self,
+    farm: &Farming,
+    staker: &Pubkey,
+    dst: &Pubkey,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(farm.farm.pubkey(), false),
+      AccountMeta::new(*staker, false),
+      AccountMeta::new(farm.treasury_reward.pubkey(), false),
+      AccountMeta::new(*dst, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self.call(pack(25, &[]), accounts, &[]).await
+  }
+
+  async fn lock_liquidity(
+    &mut self,
+    lpt: u64,
+    unlock_ts: i64,
+    schedule: LockSchedule,
+  ) -> Result<Locking, TransactionError> {
+    let locking = Locking {
+      lock: Keypair::new(),
+      escrow: Keypair::new(),
+    };
+    self.create_account(&locking.lock, Lock::LEN).await;
+    self
+      .allocate(&locking.escrow, Account::LEN, &spl_token::id())
+      .await;
+    let accounts = vec![
+      AccountMeta::new(self.payer(), true),
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(locking.lock.pubkey(), true),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new_readonly(self.mint_lpt.pubkey(), false),
+      AccountMeta::new(locking.escrow.pubkey(), true),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(sysvar::rent::id(), false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    let mut data = pack(26, &[lpt, unlock_ts as u64]);
+    data.push(schedule as u8);
+    let result = self
+      .call(data, accounts, &[&locking.lock, &locking.escrow])
+      .await;
+    result.map(|_| locking)
+  }
+
+  async fn unlock_liquidity(
+    &mut self,
+    locking: &Locking,
+    stranger: bool,
+  ) -> Result<(), TransactionError> {
+    let accounts = vec![
+      AccountMeta::new_readonly(self.pool.pubkey(), false),
+      AccountMeta::new(locking.lock.pubkey(), false),
+      AccountMeta::new(locking.escrow.pubkey(), false),
+      AccountMeta::new(self.lpt, false),
+      AccountMeta::new_readonly(self.treasurer, false),
+      AccountMeta::new_readonly(spl_token::id(), false),
+      AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    self
+      .owner_call(pack(27, &[]), accounts, stranger, &[])
+      .await
+  }
+
+  // Withdraw every LPT but the locked liquidity
+  async fn drain(&mut self) {
+    let lpt = self.balance(&self.lpt.clone()).await;
+    self.remove_liquidity(lpt, self.treasuries).await.unwrap();
+  }
+}
+
+// The schemas log while unpacking, which is only possible inside a transaction,
+// so the test side decodes the same layouts by hand
+fn read_pool(data: &[u8]) -> Pool {
+  let src = array_ref![data, 0, Pool::LEN];
+  let (
+    owner,
+    state,
+    mint_lpt,
+    vault,
+    mint_s,
+    treasury_s,
+    reserve_s,
+    mint_a,
+    treasury_a,
+    reserve_a,
+    mint_b,
+    treasury_b,
+    reserve_b,
+    version,
+    fee_min,
+    fee_max,
+    volatility,
+    referral,
+    is_permissioned,
+    max_reserve_s,
+    max_reserve_a,
+    max_reserve_b,
+    max_trade,
+    max_impact,
+    ref_slot,
+    ref_reserve_s,
+    ref_reserve_a,
+    ref_reserve_b,
+    max_outflow,
+    outflow_window,
+    window_start,
+    window_reserve_s,
+    window_reserve_a,
+    window_reserve_b,
+    outflow_s,
+    outflow_a,
+    outflow_b,
+    is_emergency,
+    burn,
+    split,
+    reserve_precision,
+    volatility_ts,
+  ) = array_refs![
+    src, 32, 1, 32, 32, 32, 32, 8, 32, 32, 8, 32, 32, 8, 1, 8, 8, 8, 8, 1, 8, 8, 8, 8, 8, 8, 8, 8,
+    8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 1, 32, 32, 1, 8
+  ];
+  Pool {
+    owner: Pubkey::new_from_array(*owner),
+    state: PoolState::try_from_primitive(state[0]).unwrap(),
+    mint_lpt: Pubkey::new_from_array(*mint_lpt),
+    vault: Pubkey::new_from_array(*vault),
+    mint_s: Pubkey::new_from_array(*mint_s),
+    treasury_s: Pubkey::new_from_array(*treasury_s),
+    reserve_s: u64::from_le_bytes(*reserve_s),
+    mint_a: Pubkey::new_from_array(*mint_a),
+    treasury_a: Pubkey::new_from_array(*treasury_a),
+    reserve_a: u64::from_le_bytes(*reserve_a),
+    mint_b: Pubkey::new_from_array(*mint_b),
+    treasury_b: Pubkey::new_from_array(*treasury_b),
+    reserve_b: u64::from_le_bytes(*reserve_b),
+    version: version[0],
+    fee_min: u64::from_le_bytes(*fee_min),
+    fee_max: u64::from_le_bytes(*fee_max),
+    volatility: u64::from_le_bytes(*volatility),
+    referral: u64::from_le_bytes(*referral),
+    is_permissioned: is_permissioned[0] == 1,
+    max_reserve_s: u64::from_le_bytes(*max_reserve_s),
+    max_reserve_a: u64::from_le_bytes(*max_reserve_a),
+    max_reserve_b: u64::from_le_bytes(*max_reserve_b),
+    max_trade: u64::from_le_bytes(*max_trade),
+    max_impact: u64::from_le_bytes(*max_impact),
+    ref_slot: u64::from_le_bytes(*ref_slot),
+    ref_reserve_s: u64::from_le_bytes(*ref_reserve_s),
+    ref_reserve_a: u64::from_le_bytes(*ref_reserve_a),
+    ref_reserve_b: u64::from_le_bytes(*ref_reserve_b),
+    max_outflow: u64::from_le_bytes(*max_outflow),
+    outflow_window: u64::from_le_bytes(*outflow_window),
+    window_start: i64::from_le_bytes(*window_start),
+    window_reserve_s: u64::from_le_bytes(*window_reserve_s),
+    window_reserve_a: u64::from_le_bytes(*window_reserve_a),
+    window_reserve_b: u64::from_le_bytes(*window_reserve_b),
+    outflow_s: u64::from_le_bytes(*outflow_s),
+    outflow_a: u64::from_le_bytes(*outflow_a),
+    outflow_b: u64::from_le_bytes(*outflow_b),
+    is_emergency: is_emergency[0] == 1,
+    burn: Pubkey::new_from_array(*burn),
+    split: Pubkey::new_from_array(*split),
+    reserve_precision: reserve_precision[0],
+    volatility_ts: i64::from_le_bytes(*volatility_ts),
+  }
+}
+
+fn read_timelock(data: &[u8]) -> Timelock {
+  let src = array_ref![data, 0, Timelock::LEN];
+  let (pool, target, state, eta, action) = array_refs![src, 32, 32, 1, 8, Action::LEN];
+  Timelock {
+    pool: Pubkey::new_from_array(*pool),
+    target: Pubkey::new_from_array(*target),
+    state: TimelockState::try_from_primitive(state[0]).unwrap(),
+    eta: i64::from_le_bytes(*eta),
+    action: Action::unpack(action).unwrap(),
+  }
+}
+
+fn read_whitelist(data: &[u8]) -> Whitelist {
+  let src = array_ref![data, 0, Whitelist::LEN];
+  let (pool, is_initialized, src_entries) = array_refs![src, 32, 1, MAX_WHITELIST * 40];
+  let mut entries = [Entry::default(); MAX_WHITELIST];
+  for (entry, src_entry) in entries.iter_mut().zip(src_entries.chunks(40)) {
+    let (trader, discount) = array_refs![array_ref![src_entry, 0, 40], 32, 8];
+    *entry = Entry {
+      trader: Pubkey::new_from_array(*trader),
+      discount: u64::from_le_bytes(*discount),
+    };
+  }
+  Whitelist {
+    pool: Pubkey::new_from_array(*pool),
+    is_initialized: is_initialized[0] == 1,
+    entries,
+  }
+}
+
+fn pack(tag: u8, args: &[u64]) -> Vec<u8> {
+  let mut data = vec![tag];
+  for arg in args {
+    data.extend_from_slice(&arg.to_le_bytes());
+  }
+  data
+}
+
+// The pool key must give an off-curve treasurer
+fn pool_keypair(program_id: &Pubkey) -> (Keypair, Pubkey) {
+  loop {
+    let pool = Keypair::new();
+    if let Ok(treasurer) = Pubkey::create_program_address(&[&pool.pubkey().to_bytes()], program_id)
+    {
+      return (pool, treasurer);
+    }
+  }
+}
+
+fn clone(keypair: &Keypair) -> Keypair {
+  Keypair::from_bytes(&keypair.to_bytes()).unwrap()
+}
+
+fn app_error(error: AppError) -> TransactionError {
+  TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+///
+/// InitializePool
+///
+
+#[tokio::test]
+async fn initialize_pool_locks_minimum_liquidity() {
+  let mut env = Env::initialized().await;
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.owner, env.payer());
+  assert_eq!(pool_data.state, PoolState::Initialized);
+  assert_eq!(pool_data.treasury_s, env.treasuries[0]);
+  assert_eq!(pool_data.reserve_s, RESERVE);
+  assert_eq!(pool_data.reserve_a, RESERVE);
+  assert_eq!(pool_data.reserve_b, RESERVE);
+  for treasury in env.treasuries.clone().iter() {
+    assert_eq!(env.balance(treasury).await, RESERVE);
+  }
+  assert_eq!(
+    env.balance(&env.lpt.clone()).await,
+    RESERVE - MINIMUM_LIQUIDITY
+  );
+  assert_eq!(env.balance(&env.burn.clone()).await, MINIMUM_LIQUIDITY);
+  assert_eq!(env.pool_data().await.burn, env.burn);
+  assert_eq!(env.supply().await, RESERVE);
+}
+
+#[tokio::test]
+async fn initialize_pool_only_once() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.initialize_pool(RESERVE, RESERVE, RESERVE + 1).await,
+    Err(app_error(AppError::ConstructorOnce))
+  );
+}
+
+#[tokio::test]
+async fn initialize_pool_rejects_zero_and_small_reserves() {
+  let mut env = Env::new().await;
+  assert_eq!(
+    env.initialize_pool(RESERVE, 0, RESERVE).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env
+      .initialize_pool(RESERVE, RESERVE, minimum_reserve(9, RESERVE_PRECISION) - 1)
+      .await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+}
+
+#[tokio::test]
+async fn initialize_pool_scales_the_minimum_reserve_by_decimals() {
+  let mut env = Env::new().await;
+  // Swap token B for one of 6 decimals, whose minimum reserve is 1000
+  let (mint, src) = env.create_mint(6).await;
+  env.mints[2] = mint;
+  env.srcs[2] = src;
+  env.treasuries[2] = get_associated_token_address(&env.treasurer, &mint);
+  assert_eq!(
+    env.initialize_pool(RESERVE, RESERVE, 999).await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+  env.initialize_pool(RESERVE, RESERVE, 1000).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_b, 1000);
+  assert_eq!(pool_data.reserve_precision, RESERVE_PRECISION);
+}
+
+#[tokio::test]
+async fn initialize_pool_requires_more_than_the_locked_liquidity() {
+  // Swap token S for one of 6 decimals, whose minimum reserve equals the locked liquidity
+  let mut env = Env::new().await;
+  let (mint, src) = env.create_mint(6).await;
+  env.mints[0] = mint;
+  env.srcs[0] = src;
+  env.treasuries[0] = get_associated_token_address(&env.treasurer, &mint);
+  assert_eq!(
+    env
+      .initialize_pool(MINIMUM_LIQUIDITY, RESERVE, RESERVE)
+      .await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+  env
+    .initialize_pool(MINIMUM_LIQUIDITY + 1, RESERVE, RESERVE)
+    .await
+    .unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, 1);
+  // Below 6 decimals the minimum reserve is under the locked liquidity
+  let mut env = Env::new().await;
+  let (mint, src) = env.create_mint(3).await;
+  env.mints[0] = mint;
+  env.srcs[0] = src;
+  env.treasuries[0] = get_associated_token_address(&env.treasurer, &mint);
+  assert_eq!(
+    env
+      .initialize_pool(MINIMUM_LIQUIDITY - 1, RESERVE, RESERVE)
+      .await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+}
+
+#[tokio::test]
+async fn initialize_pool_sets_the_reserve_precision() {
+  let mut env = Env::new().await;
+  let (mint, src) = env.create_mint(6).await;
+  env.mints[2] = mint;
+  env.srcs[2] = src;
+  env.treasuries[2] = get_associated_token_address(&env.treasurer, &mint);
+  // Zero precision asks for one whole token
+  assert_eq!(
+    env
+      .initialize_pool_with(RESERVE, RESERVE, 999_999, Some(0))
+      .await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+  env
+    .initialize_pool_with(RESERVE, RESERVE, 1_000_000, Some(0))
+    .await
+    .unwrap();
+  assert_eq!(env.pool_data().await.reserve_precision, 0);
+}
+
+///
+/// AddLiquidity & RemoveLiquidity
+///
+
+#[tokio::test]
+async fn add_and_remove_liquidity() {
+  let mut env = Env::initialized().await;
+  let lpt = env.lpt;
+  env
+    .add_liquidity(RESERVE, RESERVE, RESERVE, env.treasuries)
+    .await
+    .unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_s, 2 * RESERVE);
+  // Minted LPT is rounded down in favour of the pool
+  let minted = env.supply().await - RESERVE;
+  assert!(minted <= RESERVE && minted > RESERVE - 10);
+  assert_eq!(
+    env.balance(&lpt).await,
+    RESERVE + minted - MINIMUM_LIQUIDITY
+  );
+
+  env.remove_liquidity(minted, env.treasuries).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert!(pool_data.reserve_s >= RESERVE);
+  assert!(pool_data.reserve_a >= RESERVE);
+  assert!(pool_data.reserve_b >= RESERVE);
+  assert_eq!(env.balance(&lpt).await, RESERVE - MINIMUM_LIQUIDITY);
+  assert!(env.balance(&env.srcs[1].clone()).await <= BALANCE - RESERVE);
+}
+
+#[tokio::test]
+async fn add_liquidity_rejects_zero_and_mismatched_treasuries() {
+  let mut env = Env::initialized().await;
+  let [s, a, b] = env.treasuries;
+  assert_eq!(
+    env.add_liquidity(0, 0, 0, env.treasuries).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env
+      .add_liquidity(RESERVE, RESERVE, RESERVE, [s, b, a])
+      .await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+}
+
+#[tokio::test]
+async fn remove_liquidity_rejects_zero_frozen_and_mismatched_treasuries() {
+  let mut env = Env::initialized().await;
+  let [s, a, b] = env.treasuries;
+  assert_eq!(
+    env.remove_liquidity(0, env.treasuries).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.remove_liquidity(RESERVE / 2, [s, b, a]).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(
+    env.remove_liquidity(RESERVE / 2, env.treasuries).await,
+    Err(app_error(AppError::FrozenPool))
+  );
+}
+
+#[tokio::test]
+async fn zap_in_deposits_a_single_token() {
+  let mut env = Env::initialized().await;
+  let [s, a, b] = env.treasuries;
+  let amount = RESERVE / 10;
+  let (expected, _, _, _) =
+    Oracle::rake(0, amount, 0, RESERVE, RESERVE, RESERVE, env.supply().await).unwrap();
+  assert_eq!(
+    env.zap_in(1, amount, expected + 1, a).await,
+    Err(app_error(AppError::ExceedLimit))
+  );
+  assert_eq!(
+    env.zap_in(1, amount, 0, b).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  assert_eq!(
+    env.zap_in(1, 0, 0, a).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  // There is no fourth token
+  let mut data = pack(35, &[amount, 0]);
+  data.insert(1, 3);
+  assert_eq!(
+    env.call(data, vec![], &[]).await,
+    Err(app_error(AppError::InvalidInstruction))
+  );
+  let lpt = env.balance(&env.lpt.clone()).await;
+  env.zap_in(1, amount, expected, a).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt + expected);
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_a, RESERVE + amount);
+  assert_eq!(pool_data.reserve_s, RESERVE);
+  // Caps and allowlists apply as to any deposit
+  env.update_caps([0, RESERVE, 0], 0, false).await.unwrap();
+  assert_eq!(
+    env.zap_in(1, 1, 0, a).await,
+    Err(app_error(AppError::ExceedReserveCap))
+  );
+  env.update_permission(true, false).await.unwrap();
+  assert_eq!(
+    env.zap_in(0, amount, 0, s).await,
+    Err(app_error(AppError::NotMember))
+  );
+}
+
+#[tokio::test]
+async fn migrate_liquidity_routes_shared_tokens_and_refunds_the_rest() {
+  let mut env = Env::initialized().await;
+  // The new pool holds S and A, the latter as its B token, but not B
+  let (mint, src) = env.create_mint(9).await;
+  let to = env
+    .another_pool(
+      [env.mints[0], mint, env.mints[1]],
+      [env.srcs[0], src, env.srcs[1]],
+    )
+    .await;
+  let lpt = RESERVE / 10;
+  let (delta_s, delta_a, delta_b) =
+    Oracle::unrake(lpt, RESERVE, RESERVE, RESERVE, env.supply().await).unwrap();
+  let supply = env.supply_of(&to.mint_lpt.pubkey()).await;
+  let (expected, _, _, _) =
+    Oracle::rake(delta_s, 0, delta_a, RESERVE, RESERVE, RESERVE, supply).unwrap();
+  assert_eq!(
+    env.migrate_liquidity(lpt, expected + 1, &to).await,
+    Err(app_error(AppError::ExceedLimit))
+  );
+  assert_eq!(
+    env.migrate_liquidity(0, 0, &to).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+
+  let balance_b = env.balance(&env.srcs[2].clone()).await;
+  let balance_lpt = env.balance(&env.lpt.clone()).await;
+  env.migrate_liquidity(lpt, expected, &to).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, balance_lpt - lpt);
+  assert_eq!(
+    env.balance(&to.lpt).await,
+    RESERVE - MINIMUM_LIQUIDITY + expected
+  );
+  assert_eq!(env.balance(&env.srcs[2].clone()).await, balance_b + delta_b);
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_s, RESERVE - delta_s);
+  assert_eq!(pool_data.reserve_a, RESERVE - delta_a);
+  assert_eq!(pool_data.reserve_b, RESERVE - delta_b);
+  let pool_data = env.pool_data_of(&to.pool.pubkey()).await;
+  assert_eq!(pool_data.reserve_s, RESERVE + delta_s);
+  assert_eq!(pool_data.reserve_a, RESERVE);
+  assert_eq!(pool_data.reserve_b, RESERVE + delta_a);
+  assert_eq!(env.balance(&to.treasuries[2]).await, RESERVE + delta_a);
+}
+
+#[tokio::test]
+async fn migrate_liquidity_rejects_the_same_pool_and_frozen_pools() {
+  let mut env = Env::initialized().await;
+  let to = Pooling {
+    pool: clone(&env.pool),
+    mint_lpt: clone(&env.mint_lpt),
+    treasurer: env.treasurer,
+    treasuries: env.treasuries,
+    lpt: env.lpt,
+  };
+  assert_eq!(
+    env.migrate_liquidity(RESERVE / 10, 0, &to).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  let to = env.another_pool(env.mints, env.srcs).await;
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(
+    env.migrate_liquidity(RESERVE / 10, 0, &to).await,
+    Err(app_error(AppError::FrozenPool))
+  );
+}
+
+///
+/// Swap
+///
+
+#[tokio::test]
+async fn swap_pays_out_and_keeps_reserves_covered() {
+  let mut env = Env::initialized().await;
+  let [treasury_s, treasury_a, treasury_b] = env.treasuries;
+  let amount = RESERVE / 100;
+  env.swap(amount, 1, 1, 2, treasury_s).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_a, RESERVE + amount);
+  assert!(pool_data.reserve_b > RESERVE - amount);
+  assert_eq!(
+    env.balance(&env.srcs[1].clone()).await,
+    BALANCE - RESERVE - amount
+  );
+  let paid = env.balance(&env.srcs[2].clone()).await - (BALANCE - RESERVE);
+  assert!(paid > 0 && paid < amount);
+  // The pool keeps the earning of the non-SEN side in the vault
+  assert!(env.balance(&env.vault.pubkey()).await > 0);
+  assert!(env.balance(&treasury_a).await >= pool_data.reserve_a);
+  assert!(env.balance(&treasury_b).await >= pool_data.reserve_b);
+}
+
+#[tokio::test]
+async fn batch_swap_matches_sequential_swaps() {
+  let amount = RESERVE / 100;
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  let src_s = env.srcs[0];
+  let mut paid_amounts = vec![];
+  for bid in 1..3 {
+    let before = env.balance(&src_s).await;
+    env.swap(amount, 1, bid, 0, treasury_s).await.unwrap();
+    paid_amounts.push(env.balance(&src_s).await - before);
+  }
+  let expected = env.pool_data().await;
+  let paid = env.balance(&src_s).await;
+
+  let mut env = Env::initialized().await;
+  let legs = [(1, 0, amount, 1), (2, 0, amount, 1)];
+  // One event sums up the batch, the paid amounts are returned too
+  let ix = env.batch_swap_ix(&legs);
+  let (logs, mut return_data) = env.simulate(&[ix]).await;
+  // The simulation trims the trailing zero bytes
+  return_data.resize(8 * legs.len(), 0);
+  let returned: Vec<u64> = return_data
+    .chunks(8)
+    .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+    .collect();
+  assert_eq!(returned, paid_amounts);
+  let event = AppEvent::BatchSwap {
+    pool: env.pool.pubkey(),
+    paid_amounts,
+  };
+  assert!(logs
+    .iter()
+    .any(|log| log.ends_with(&format!("Event: {:?}", event))));
+  env.batch_swap(&legs).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_s, expected.reserve_s);
+  assert_eq!(pool_data.reserve_a, expected.reserve_a);
+  assert_eq!(pool_data.reserve_b, expected.reserve_b);
+  assert_eq!(pool_data.volatility, expected.volatility);
+  assert_eq!(env.balance(&env.srcs[0].clone()).await, paid);
+}
+
+#[tokio::test]
+async fn batch_swap_reverts_every_leg_on_failure() {
+  let mut env = Env::initialized().await;
+  let amount = RESERVE / 100;
+  assert_eq!(
+    env.batch_swap(&[]).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env
+      .batch_swap(&[(1, 0, amount, 1), (2, 0, amount, u64::MAX)])
+      .await,
+    Err(app_error(AppError::ExceedLimit))
+  );
+  assert_eq!(
+    env.batch_swap(&[(1, 0, amount, 1), (2, 0, 0, 1)]).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_a, RESERVE);
+  assert_eq!(pool_data.reserve_s, RESERVE);
+  // The trade cap applies to each leg
+  env
+    .update_caps([0, 0, 0], DECIMALS / 100, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    env
+      .batch_swap(&[(1, 0, amount, 1), (2, 0, amount + 1, 1)])
+      .await,
+    Err(app_error(AppError::ExceedTradeCap))
+  );
+  env
+    .batch_swap(&[(1, 0, amount, 1), (2, 0, amount, 1)])
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn swap_rejects_limit_zero_frozen_and_mismatched_treasuries() {
+  let mut env = Env::initialized().await;
+  let [treasury_s, treasury_a, _] = env.treasuries;
+  let amount = RESERVE / 100;
+  assert_eq!(
+    env.swap(amount, amount, 1, 2, treasury_s).await,
+    Err(app_error(AppError::ExceedLimit))
+  );
+  assert_eq!(
+    env.swap(0, 0, 1, 2, treasury_s).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.swap(amount, 0, 1, 2, treasury_a).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(
+    env.swap(amount, 1, 1, 2, treasury_s).await,
+    Err(app_error(AppError::FrozenPool))
+  );
+}
+
+///
+/// FreezePool & ThawPool
+///
+
+#[tokio::test]
+async fn freeze_and_thaw_pool() {
+  let mut env = Env::initialized().await;
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(env.pool_data().await.state, PoolState::Frozen);
+  env.thaw_pool(false).await.unwrap();
+  assert_eq!(env.pool_data().await.state, PoolState::Initialized);
+}
+
+#[tokio::test]
+async fn freeze_and_thaw_pool_reject_wrong_owner() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.freeze_pool(true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env.thaw_pool(true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+}
+
+///
+/// Earn
+///
+
+#[tokio::test]
+async fn earn_withdraws_the_vault() {
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  let vault = env.vault.pubkey();
+  env.swap(RESERVE / 100, 1, 1, 2, treasury_s).await.unwrap();
+  let earning = env.balance(&vault).await;
+  let src_s = env.srcs[0];
+  let balance = env.balance(&src_s).await;
+  assert_eq!(
+    env.earn(0, false).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.earn(earning, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  env.earn(earning, false).await.unwrap();
+  assert_eq!(env.balance(&vault).await, 0);
+  assert_eq!(env.balance(&src_s).await, balance + earning);
+}
+
+///
+/// TransferPoolOwnership, ExecuteAction & CancelAction
+///
+
+#[tokio::test]
+async fn transfer_pool_ownership_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  let new_owner = Pubkey::new_unique();
+  let timelock = Keypair::new();
+  env
+    .transfer_pool_ownership(&new_owner, &timelock, false)
+    .await
+    .unwrap();
+  let timelock_data = env.timelock_data(&timelock.pubkey()).await;
+  assert_eq!(timelock_data.pool, env.pool.pubkey());
+  assert_eq!(timelock_data.state, TimelockState::Queued);
+  assert_eq!(
+    timelock_data.action,
+    Action::TransferPoolOwnership { new_owner }
+  );
+  assert_eq!(timelock_data.target, env.pool.pubkey());
+  // The same timelock cannot be queued twice
+  assert_eq!(
+    env
+      .transfer_pool_ownership(&Pubkey::new_unique(), &timelock, false)
+      .await,
+    Err(app_error(AppError::ConstructorOnce))
+  );
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.skip_timelock().await;
+  // The action only applies to the account it was queued for
+  assert_eq!(
+    env
+      .execute_action_on(&timelock.pubkey(), &timelock.pubkey())
+      .await,
+    Err(app_error(AppError::UnmatchedTarget))
+  );
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  assert_eq!(env.pool_data().await.owner, new_owner);
+  // Executing closes the timelock
+  assert!(env
+    .context
+    .banks_client
+    .get_account(timelock.pubkey())
+    .await
+    .unwrap()
+    .is_none());
+}
+
+#[tokio::test]
+async fn queued_action_expires_after_the_grace_period() {
+  let mut env = Env::initialized().await;
+  let timelock = Keypair::new();
+  env
+    .transfer_pool_ownership(&Pubkey::new_unique(), &timelock, false)
+    .await
+    .unwrap();
+  env.warp(TIMELOCK_DELAY + GRACE_PERIOD + 1).await;
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::ExpiredAction))
+  );
+  assert_eq!(env.pool_data().await.owner, env.payer());
+  // An expired action can still be cancelled to refund the rent
+  env.cancel_action(&timelock.pubkey()).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_pool_ownership_rejects_wrong_owner() {
+  let mut env = Env::initialized().await;
+  let timelock = Keypair::new();
+  assert_eq!(
+    env
+      .transfer_pool_ownership(&Pubkey::new_unique(), &timelock, true)
+      .await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+}
+
+#[tokio::test]
+async fn cancelled_action_cannot_be_executed() {
+  let mut env = Env::initialized().await;
+  let timelock = Keypair::new();
+  env
+    .transfer_pool_ownership(&Pubkey::new_unique(), &timelock, false)
+    .await
+    .unwrap();
+  env.cancel_action(&timelock.pubkey()).await.unwrap();
+  // Cancelling closes the timelock
+  assert!(env
+    .context
+    .banks_client
+    .get_account(timelock.pubkey())
+    .await
+    .unwrap()
+    .is_none());
+  env.skip_timelock().await;
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::IncorrectProgramId))
+  );
+  assert_eq!(env.pool_data().await.owner, env.payer());
+}
+
+///
+/// UpdateFees
+///
+
+#[tokio::test]
+async fn update_fees_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  let pool_data = env.pool_data().await;
+  assert_eq!((pool_data.fee_min, pool_data.fee_max), (FEE, FEE));
+  let timelock = Keypair::new();
+  assert_eq!(
+    env.update_fees(FEE, MAX_FEE, &timelock, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env.update_fees(MAX_FEE, FEE, &timelock, false).await,
+    Err(app_error(AppError::InvalidFee))
+  );
+  assert_eq!(
+    env.update_fees(FEE, MAX_FEE + 1, &timelock, false).await,
+    Err(app_error(AppError::InvalidFee))
+  );
+  env
+    .update_fees(FEE, MAX_FEE, &timelock, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!((pool_data.fee_min, pool_data.fee_max), (FEE, MAX_FEE));
+}
+
+#[tokio::test]
+async fn swap_fee_rises_with_volatility() {
+  let mut env = Env::initialized().await;
+  let timelock = Keypair::new();
+  env
+    .update_fees(FEE, MAX_FEE, &timelock, false)
+    .await
+    .unwrap();
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  let treasury_s = env.treasuries[0];
+  let dst = env.srcs[2];
+  let amount = RESERVE / 10;
+  let mut paid = vec![];
+  for _ in 0..3 {
+    // Trade back and forth so the price stays around the same level
+    let before = env.balance(&dst).await;
+    env.swap(amount, 1, 1, 2, treasury_s).await.unwrap();
+    paid.push(env.balance(&dst).await - before);
+    let received = *paid.last().unwrap();
+    env.swap(received, 1, 2, 1, treasury_s).await.unwrap();
+  }
+  assert!(env.pool_data().await.volatility > 0);
+  assert!(paid[2] < paid[0]);
+}
+
+#[tokio::test]
+async fn swap_volatility_decays_while_idle() {
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  env.swap(RESERVE / 10, 1, 1, 2, treasury_s).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert!(pool_data.volatility > 0);
+  assert_eq!(pool_data.volatility_ts, env.now().await);
+  // A dust trade after a long idle time finds the pool calm again
+  env.warp(64 * VOLATILITY_HALF_LIFE).await;
+  env.swap(1000, 0, 1, 2, treasury_s).await.unwrap();
+  let calm = env.pool_data().await;
+  assert!(calm.volatility < pool_data.volatility / 100);
+  assert_eq!(calm.volatility_ts, env.now().await);
+}
+
+///
+/// InitializeWhitelist & SetDiscount
+///
+
+#[tokio::test]
+async fn initialize_whitelist_only_once_by_the_owner() {
+  let mut env = Env::initialized().await;
+  let whitelist = Keypair::new();
+  assert_eq!(
+    env.initialize_whitelist(&whitelist, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  env.initialize_whitelist(&whitelist, false).await.unwrap();
+  assert_eq!(
+    env.initialize_whitelist(&whitelist, false).await,
+    Err(app_error(AppError::ConstructorOnce))
+  );
+}
+
+#[tokio::test]
+async fn set_discount_rejects_wrong_owner_and_invalid_discount() {
+  let mut env = Env::initialized().await;
+  let whitelist = Keypair::new();
+  env.initialize_whitelist(&whitelist, false).await.unwrap();
+  let trader = env.payer();
+  assert_eq!(
+    env
+      .set_discount(&whitelist.pubkey(), &trader, DECIMALS / 2, true)
+      .await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env
+      .set_discount(&whitelist.pubkey(), &trader, DECIMALS + 1, false)
+      .await,
+    Err(app_error(AppError::InvalidFee))
+  );
+}
+
+#[tokio::test]
+async fn set_discount_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  let whitelist = Keypair::new();
+  env.initialize_whitelist(&whitelist, false).await.unwrap();
+  let trader = env.payer();
+  let timelock = Keypair::new();
+  let accounts = vec![
+    AccountMeta::new_readonly(env.pool.pubkey(), false),
+    AccountMeta::new_readonly(whitelist.pubkey(), false),
+    AccountMeta::new_readonly(trader, false),
+  ];
+  env
+    .queue_call(pack(17, &[DECIMALS]), accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env
+      .execute_action_on(&timelock.pubkey(), &whitelist.pubkey())
+      .await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.skip_timelock().await;
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::UnmatchedTarget))
+  );
+  env
+    .execute_action_on(&timelock.pubkey(), &whitelist.pubkey())
+    .await
+    .unwrap();
+  let whitelist_data = env.whitelist_data(&whitelist.pubkey()).await;
+  assert_eq!(whitelist_data.get_discount(&trader), DECIMALS);
+}
+
+#[tokio::test]
+async fn set_discount_fills_up_the_whitelist() {
+  let mut env = Env::initialized().await;
+  let whitelist = Keypair::new();
+  env.initialize_whitelist(&whitelist, false).await.unwrap();
+  let traders: Vec<Pubkey> = (0..MAX_WHITELIST).map(|_| Pubkey::new_unique()).collect();
+  for trader in &traders {
+    env
+      .set_discount(&whitelist.pubkey(), trader, DECIMALS, false)
+      .await
+      .unwrap();
+  }
+  let newcomer = Pubkey::new_unique();
+  assert_eq!(
+    env
+      .set_discount(&whitelist.pubkey(), &newcomer, DECIMALS, false)
+      .await,
+    Err(app_error(AppError::FullWhitelist))
+  );
+  // Removing a trader frees a slot
+  env
+    .set_discount(&whitelist.pubkey(), &traders[0], 0, false)
+    .await
+    .unwrap();
+  env
+    .set_discount(&whitelist.pubkey(), &newcomer, DECIMALS, false)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn swap_applies_the_whitelist_discount() {
+  let mut env = Env::initialized().await;
+  let whitelist = Keypair::new();
+  env.initialize_whitelist(&whitelist, false).await.unwrap();
+  let treasury_s = env.treasuries[0];
+  let dst = env.srcs[2];
+  let amount = RESERVE / 100;
+  // An unlisted trader pays the full fee
+  let before = env.balance(&dst).await;
+  env
+    .swap_with(
+      amount,
+      1,
+      1,
+      2,
+      treasury_s,
+      &[(WITH_WHITELIST, whitelist.pubkey())],
+    )
+    .await
+    .unwrap();
+  let full = env.balance(&dst).await - before;
+  env.swap(full, 1, 2, 1, treasury_s).await.unwrap();
+  // A fee-exempt trader gets the raw output of the curve
+  let trader = env.payer();
+  env
+    .set_discount(&whitelist.pubkey(), &trader, DECIMALS, false)
+    .await
+    .unwrap();
+  let pool_data = env.pool_data().await;
+  let raw = pool_data.reserve_b
+    - Oracle::curve(
+      pool_data.reserve_a + amount,
+      pool_data.reserve_a,
+      pool_data.reserve_b,
+    )
+    .unwrap();
+  let vault = env.balance(&env.vault.pubkey()).await;
+  let before = env.balance(&dst).await;
+  env
+    .swap_with(
+      amount,
+      1,
+      1,
+      2,
+      treasury_s,
+      &[(WITH_WHITELIST, whitelist.pubkey())],
+    )
+    .await
+    .unwrap();
+  let exempted = env.balance(&dst).await - before;
+  assert_eq!(exempted, raw);
+  assert!(exempted > full);
+  assert_eq!(env.balance(&env.vault.pubkey()).await, vault);
+}
+
+///
+/// UpdateReferral
+///
+
+#[tokio::test]
+async fn update_referral_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.update_referral(DECIMALS / 2, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env.update_referral(DECIMALS + 1, false).await,
+    Err(app_error(AppError::InvalidRatio))
+  );
+  let timelock = Keypair::new();
+  let accounts = vec![AccountMeta::new(env.pool.pubkey(), false)];
+  env
+    .queue_call(pack(18, &[DECIMALS / 2]), accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  assert_eq!(env.pool_data().await.referral, 0);
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  assert_eq!(env.pool_data().await.referral, DECIMALS / 2);
+}
+
+#[tokio::test]
+async fn swap_shares_the_earning_with_the_referrer() {
+  let mut env = Env::initialized().await;
+  env.update_referral(DECIMALS / 2, false).await.unwrap();
+  let treasury_s = env.treasuries[0];
+  let stranger = env.stranger.pubkey();
+  let referrer = env.open_account(&stranger, 0).await;
+  let wrong_referrer = env.open_account(&stranger, 1).await;
+  let amount = RESERVE / 100;
+  assert_eq!(
+    env
+      .swap_with(
+        amount,
+        1,
+        1,
+        2,
+        treasury_s,
+        &[(WITH_REFERRER, wrong_referrer)]
+      )
+      .await,
+    Err(app_error(AppError::InvalidMint))
+  );
+  // The payer cannot refer itself
+  let own_referrer = env.srcs[0];
+  assert_eq!(
+    env
+      .swap_with(
+        amount,
+        1,
+        1,
+        2,
+        treasury_s,
+        &[(WITH_REFERRER, own_referrer)]
+      )
+      .await,
+    Err(app_error(AppError::SelfReferral))
+  );
+  env
+    .swap_with(amount, 1, 1, 2, treasury_s, &[(WITH_REFERRER, referrer)])
+    .await
+    .unwrap();
+  let referral = env.balance(&referrer).await;
+  let vault = env.balance(&env.vault.pubkey()).await;
+  // The referrer gets half of the earning, rounded down
+  assert!(referral > 0);
+  assert!(vault == referral || vault == referral + 1);
+  // Without a referrer the vault keeps the whole earning
+  env.swap(amount, 1, 1, 2, treasury_s).await.unwrap();
+  assert_eq!(env.balance(&referrer).await, referral);
+  assert!(env.balance(&env.vault.pubkey()).await > vault + referral);
+}
+
+///
+/// SetSplit & DistributeEarnings
+///
+
+#[tokio::test]
+async fn set_split_rejects_wrong_owner_and_invalid_shares() {
+  let mut env = Env::initialized().await;
+  let split = Keypair::new();
+  let stranger = env.stranger.pubkey();
+  let beneficiary = env.open_account(&stranger, 0).await;
+  let wrong_beneficiary = env.open_account(&stranger, 1).await;
+  assert_eq!(
+    env
+      .set_split(&split, &[DECIMALS], &[beneficiary], true)
+      .await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env
+      .set_split(
+        &split,
+        &[DECIMALS / 2, DECIMALS / 4],
+        &[beneficiary, beneficiary],
+        false
+      )
+      .await,
+    Err(app_error(AppError::InvalidRatio))
+  );
+  assert_eq!(
+    env
+      .set_split(&split, &[DECIMALS, 0], &[beneficiary, beneficiary], false)
+      .await,
+    Err(app_error(AppError::InvalidRatio))
+  );
+  assert_eq!(
+    env.set_split(&split, &[DECIMALS], &[], false).await,
+    Err(app_error(AppError::InvalidBeneficiary))
+  );
+  assert_eq!(
+    env
+      .set_split(&split, &[DECIMALS], &[wrong_beneficiary], false)
+      .await,
+    Err(app_error(AppError::InvalidMint))
+  );
+  env
+    .set_split(&split, &[DECIMALS], &[beneficiary], false)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn set_split_waits_for_the_timelock_then_disables_earn() {
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  env.swap(RESERVE / 100, 1, 1, 2, treasury_s).await.unwrap();
+  let split = Keypair::new();
+  let stranger = env.stranger.pubkey();
+  let beneficiary = env.open_account(&stranger, 0).await;
+  let timelock = Keypair::new();
+  env
+    .queue_split(&split, &[DECIMALS], &[beneficiary], false, &timelock)
+    .await
+    .unwrap();
+  // The queued split pays nothing yet and the owner may still earn
+  assert_eq!(
+    env
+      .distribute_earnings(&split.pubkey(), &[beneficiary])
+      .await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  assert_eq!(
+    env
+      .execute_action_on(&timelock.pubkey(), &split.pubkey())
+      .await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.earn(1, false).await.unwrap();
+  env.skip_timelock().await;
+  env
+    .execute_action_on(&timelock.pubkey(), &split.pubkey())
+    .await
+    .unwrap();
+  assert_eq!(env.pool_data().await.split, split.pubkey());
+  // The vault now only leaves through the split
+  assert_eq!(
+    env.earn(1, false).await,
+    Err(app_error(AppError::SharedVault))
+  );
+  let vault = env.balance(&env.vault.pubkey()).await;
+  env
+    .distribute_earnings(&split.pubkey(), &[beneficiary])
+    .await
+    .unwrap();
+  assert_eq!(env.balance(&beneficiary).await, vault);
+}
+
+#[tokio::test]
+async fn distribute_earnings_pays_each_beneficiary() {
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  for _ in 0..3 {
+    env.swap(RESERVE / 10, 1, 1, 2, treasury_s).await.unwrap();
+  }
+  let split = Keypair::new();
+  let stranger = env.stranger.pubkey();
+  let beneficiaries = [
+    env.open_account(&stranger, 0).await,
+    env.open_account(&Pubkey::new_unique(), 0).await,
+    env.open_account(&Pubkey::new_unique(), 0).await,
+  ];
+  let ratios = [DECIMALS / 2, DECIMALS * 3 / 10, DECIMALS / 5];
+  env
+    .set_split(&split, &ratios, &beneficiaries, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    env
+      .distribute_earnings(
+        &split.pubkey(),
+        &[beneficiaries[1], beneficiaries[0], beneficiaries[2]]
+      )
+      .await,
+    Err(app_error(AppError::InvalidBeneficiary))
+  );
+  let vault = env.balance(&env.vault.pubkey()).await;
+  assert!(vault > 0);
+  env
+    .distribute_earnings(&split.pubkey(), &beneficiaries)
+    .await
+    .unwrap();
+  let mut paid = 0;
+  for (beneficiary, ratio) in beneficiaries.iter().zip(ratios.iter()) {
+    let expected = (vault as u128 * *ratio as u128 / DECIMALS as u128) as u64;
+    assert_eq!(env.balance(beneficiary).await, expected);
+    paid += expected;
+  }
+  // Only the rounding dust is left
+  assert_eq!(env.balance(&env.vault.pubkey()).await, vault - paid);
+  assert!(vault - paid < beneficiaries.len() as u64);
+}
+
+///
+/// Farming
+///
+
+#[tokio::test]
+async fn initialize_farm_only_by_the_owner() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.initialize_farm(1000, true).await.err(),
+    Some(app_error(AppError::InvalidOwner))
+  );
+  let farm = env.initialize_farm(1000, false).await.unwrap();
+  assert_eq!(
+    env.update_reward_rate(&farm, 2000, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  env.update_reward_rate(&farm, 2000, false).await.unwrap();
+}
+
+#[tokio::test]
+async fn update_reward_rate_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  let farm = env.initialize_farm(1000, false).await.unwrap();
+  let timelock = Keypair::new();
+  let accounts = vec![
+    AccountMeta::new_readonly(env.pool.pubkey(), false),
+    AccountMeta::new_readonly(farm.farm.pubkey(), false),
+  ];
+  env
+    .queue_call(pack(22, &[2000]), accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env
+      .execute_action_on(&timelock.pubkey(), &farm.farm.pubkey())
+      .await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.skip_timelock().await;
+  env
+    .execute_action_on(&timelock.pubkey(), &farm.farm.pubkey())
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn stake_accrues_rewards_over_time() {
+  let mut env = Env::initialized().await;
+  let rate = 1000;
+  let farm = env.initialize_farm(rate, false).await.unwrap();
+  env.fund_farm(&farm, BALANCE / 10).await;
+  let staker = Keypair::new();
+  let lpt = env.balance(&env.lpt.clone()).await;
+  assert_eq!(
+    env.stake(&farm, &staker, 0).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  env.stake(&farm, &staker, lpt / 2).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt - lpt / 2);
+  assert_eq!(env.balance(&farm.treasury_lpt.pubkey()).await, lpt / 2);
+
+  let dst = env.srcs[0];
+  let before = env.balance(&dst).await;
+  env.warp(100).await;
+  env.harvest(&farm, &staker.pubkey(), &dst).await.unwrap();
+  let reward = env.balance(&dst).await - before;
+  // The only staker gets the whole emission, less the rounding
+  assert!(reward <= 100 * rate && reward + 1 >= 100 * rate);
+  assert_eq!(
+    env.harvest(&farm, &staker.pubkey(), &dst).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+
+  assert_eq!(
+    env.unstake(&farm, &staker.pubkey(), lpt).await,
+    Err(app_error(AppError::InsufficientFunds))
+  );
+  env.unstake(&farm, &staker.pubkey(), lpt / 2).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt);
+  // Nothing accrues once unstaked
+  env.warp(100).await;
+  assert_eq!(
+    env.harvest(&farm, &staker.pubkey(), &dst).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+}
+
+#[tokio::test]
+async fn stakers_share_rewards_by_stake() {
+  let mut env = Env::initialized().await;
+  let rate = 1000000;
+  let farm = env.initialize_farm(rate, false).await.unwrap();
+  env.fund_farm(&farm, BALANCE / 10).await;
+  let (big, small) = (Keypair::new(), Keypair::new());
+  let lpt = env.balance(&env.lpt.clone()).await / 4;
+  env.create_account(&big, Staker::LEN).await;
+  env.create_account(&small, Staker::LEN).await;
+  // Both stake at the same time
+  let ixs = [
+    env.stake_ix(&farm, &big.pubkey(), 3 * lpt),
+    env.stake_ix(&farm, &small.pubkey(), lpt),
+  ];
+  env.execute(&ixs, &[&big, &small]).await.unwrap();
+  env.warp(100).await;
+  let (dst_big, dst_small) = (
+    env.srcs[0],
+    env.open_account(&env.stranger.pubkey(), 0).await,
+  );
+  let before = env.balance(&dst_big).await;
+  env.harvest(&farm, &big.pubkey(), &dst_big).await.unwrap();
+  env
+    .harvest(&farm, &small.pubkey(), &dst_small)
+    .await
+    .unwrap();
+  let reward_big = env.balance(&dst_big).await - before;
+  let reward_small = env.balance(&dst_small).await;
+  assert!(reward_big <= 75 * rate && reward_big + 1 >= 75 * rate);
+  assert!(reward_small <= 25 * rate && reward_small + 1 >= 25 * rate);
+  // A stranger cannot move the stake of another owner
+  let stranger = clone(&env.stranger);
+  let mut ix = env.stake_ix(&farm, &big.pubkey(), 1);
+  ix.accounts[0] = AccountMeta::new(stranger.pubkey(), true);
+  ix.accounts[2].is_signer = false;
+  assert_eq!(
+    env.execute(&[ix], &[&stranger]).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+}
+
+#[tokio::test]
+async fn harvest_pays_what_an_underfunded_farm_holds() {
+  let mut env = Env::initialized().await;
+  let rate = 1000;
+  let farm = env.initialize_farm(rate, false).await.unwrap();
+  env.fund_farm(&farm, 10 * rate).await;
+  let staker = Keypair::new();
+  let lpt = env.balance(&env.lpt.clone()).await;
+  env.stake(&farm, &staker, lpt).await.unwrap();
+  env.warp(100).await;
+  let dst = env.open_account(&env.stranger.pubkey(), 0).await;
+  env.harvest(&farm, &staker.pubkey(), &dst).await.unwrap();
+  assert_eq!(env.balance(&dst).await, 10 * rate);
+  // The rest, accrued until the new rate applies, is paid once the farm is funded again
+  env.update_reward_rate(&farm, 0, false).await.unwrap();
+  let accrued = (100 + TIMELOCK_DELAY as u64) * rate;
+  env.fund_farm(&farm, accrued).await;
+  env.harvest(&farm, &staker.pubkey(), &dst).await.unwrap();
+  // Less the rounding of each harvest
+  let reward = env.balance(&dst).await;
+  assert!(reward <= accrued && reward + 2 >= accrued);
+}
+
+///
+/// UpdateCaps
+///
+
+#[tokio::test]
+async fn update_caps_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.update_caps([0; 3], DECIMALS / 10, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env.update_caps([0; 3], DECIMALS + 1, false).await,
+    Err(app_error(AppError::InvalidRatio))
+  );
+  let timelock = Keypair::new();
+  let accounts = vec![AccountMeta::new(env.pool.pubkey(), false)];
+  env
+    .queue_call(
+      pack(31, &[1, 2, 3, DECIMALS / 10]),
+      accounts,
+      false,
+      &timelock,
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  assert_eq!(env.pool_data().await.max_trade, 0);
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(
+    [
+      pool_data.max_reserve_s,
+      pool_data.max_reserve_a,
+      pool_data.max_reserve_b
+    ],
+    [1, 2, 3]
+  );
+  assert_eq!(pool_data.max_trade, DECIMALS / 10);
+}
+
+#[tokio::test]
+async fn add_liquidity_stops_at_the_reserve_cap() {
+  let mut env = Env::initialized().await;
+  env
+    .update_caps([0, 2 * RESERVE, RESERVE], 0, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.add_liquidity(0, RESERVE + 1, 0, env.treasuries).await,
+    Err(app_error(AppError::ExceedReserveCap))
+  );
+  env
+    .add_liquidity(0, RESERVE, 0, env.treasuries)
+    .await
+    .unwrap();
+  // Reserve B sits at its cap, depositing it grows it beyond
+  assert_eq!(
+    env.add_liquidity(1, 1, 1, env.treasuries).await,
+    Err(app_error(AppError::ExceedReserveCap))
+  );
+  // Uncapped and untouched reserves do not matter
+  env
+    .add_liquidity(RESERVE, 0, 0, env.treasuries)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn swap_stops_at_the_trade_cap() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  env
+    .update_caps([0; 3], DECIMALS / 100, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.swap(RESERVE / 100 + 1, 0, 1, 0, s).await,
+    Err(app_error(AppError::ExceedTradeCap))
+  );
+  env.swap(RESERVE / 100, 0, 1, 0, s).await.unwrap();
+  env.update_caps([0; 3], 0, false).await.unwrap();
+  env.swap(RESERVE / 10, 0, 1, 0, s).await.unwrap();
+}
+
+///
+/// UpdateCircuitBreaker
+///
+
+#[tokio::test]
+async fn circuit_breaker_bounds_the_price_move_within_a_slot() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  assert_eq!(
+    env.update_circuit_breaker(DECIMALS / 100, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  let timelock = Keypair::new();
+  let accounts = vec![AccountMeta::new(env.pool.pubkey(), false)];
+  env
+    .queue_call(pack(32, &[DECIMALS / 100]), accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  // Bidding 1% of the reserve moves the price by about 2%
+  assert_eq!(
+    env.swap(RESERVE / 100, 0, 1, 0, s).await,
+    Err(app_error(AppError::ExceedPriceImpact))
+  );
+  // Small swaps add up against the price at the start of the slot
+  env.swap(RESERVE / 400, 0, 1, 0, s).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.ref_reserve_a, RESERVE);
+  assert_eq!(
+    env.swap(RESERVE / 400, 0, 1, 0, s).await,
+    Err(app_error(AppError::ExceedPriceImpact))
+  );
+  // Swapping back is always within bounds
+  env.swap(RESERVE / 400, 0, 0, 1, s).await.unwrap();
+  env.next_slot().await;
+  env.swap(RESERVE / 400, 0, 1, 0, s).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_ne!(pool_data.ref_reserve_a, RESERVE);
+  // Zero disables the breaker
+  env.update_circuit_breaker(0, false).await.unwrap();
+  env.swap(RESERVE / 100, 0, 1, 0, s).await.unwrap();
+}
+
+#[tokio::test]
+async fn circuit_breaker_counts_moves_of_other_instructions() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  env
+    .update_circuit_breaker(DECIMALS / 100, false)
+    .await
+    .unwrap();
+  env.next_slot().await;
+  // A donation records the reference before moving the price
+  env
+    .donate(0, RESERVE / 100, 0, env.treasuries)
+    .await
+    .unwrap();
+  let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.ref_slot, clock.slot);
+  assert_eq!(pool_data.ref_reserve_a, RESERVE);
+  assert_eq!(
+    env.swap(RESERVE / 400, 0, 1, 0, s).await,
+    Err(app_error(AppError::ExceedPriceImpact))
+  );
+  env.next_slot().await;
+  env.swap(RESERVE / 400, 0, 1, 0, s).await.unwrap();
+}
+
+///
+/// UpdateRateLimit & UpdateEmergency
+///
+
+#[tokio::test]
+async fn update_rate_limit_waits_for_the_timelock() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.update_rate_limit(DECIMALS / 10, 3600, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  assert_eq!(
+    env.update_rate_limit(DECIMALS + 1, 3600, false).await,
+    Err(app_error(AppError::InvalidRatio))
+  );
+  assert_eq!(
+    env.update_rate_limit(DECIMALS / 10, 0, false).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.update_emergency(true, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  // The limit waits for the timelock, the emergency flag does not
+  let timelock = Keypair::new();
+  let accounts = vec![AccountMeta::new(env.pool.pubkey(), false)];
+  env
+    .queue_call(pack(33, &[DECIMALS / 10, 3600]), accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  env.update_emergency(true, false).await.unwrap();
+  assert!(env.pool_data().await.is_emergency);
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  assert_eq!(env.pool_data().await.max_outflow, DECIMALS / 10);
+}
+
+#[tokio::test]
+async fn swap_earnings_count_against_the_sen_outflow() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  env
+    .update_rate_limit(DECIMALS / 10, 3600, false)
+    .await
+    .unwrap();
+  env.swap(RESERVE / 100, 0, 1, 2, s).await.unwrap();
+  let earning = env.balance(&env.vault.pubkey()).await;
+  assert!(earning > 0);
+  assert_eq!(env.pool_data().await.outflow_s, earning);
+}
+
+#[tokio::test]
+async fn outflows_are_limited_per_window() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  let lpt = env.balance(&env.lpt.clone()).await;
+  env
+    .update_rate_limit(DECIMALS / 10, 3600, false)
+    .await
+    .unwrap();
+  // Withdrawals and swap payouts add up to 10% of each reserve per window
+  env
+    .remove_liquidity(lpt / 15, env.treasuries)
+    .await
+    .unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.window_reserve_a, RESERVE);
+  assert_eq!(
+    env.remove_liquidity(lpt / 15, env.treasuries).await,
+    Err(app_error(AppError::ExceedOutflow))
+  );
+  assert_eq!(
+    env.swap(RESERVE / 10, 0, 0, 1, s).await,
+    Err(app_error(AppError::ExceedOutflow))
+  );
+  env.swap(RESERVE / 50, 0, 0, 1, s).await.unwrap();
+  // A new window starts from the current reserves
+  env.warp(3600).await;
+  env
+    .remove_liquidity(lpt / 15, env.treasuries)
+    .await
+    .unwrap();
+  // The emergency exit is not limited, swaps still are
+  env.update_emergency(true, false).await.unwrap();
+  env.remove_liquidity(lpt / 2, env.treasuries).await.unwrap();
+  assert_eq!(
+    env.swap(RESERVE / 10, 0, 0, 1, s).await,
+    Err(app_error(AppError::ExceedOutflow))
+  );
+}
+
+///
+/// UpdatePermission, AddMember & RemoveMember
+///
+
+#[tokio::test]
+async fn permissioned_pool_serves_members_only() {
+  let mut env = Env::initialized().await;
+  let s = env.treasuries[0];
+  let payer = env.payer();
+  let entry = env.member(&payer);
+  assert_eq!(
+    env.update_permission(true, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  let timelock = Keypair::new();
+  let accounts = vec![AccountMeta::new(env.pool.pubkey(), false)];
+  env
+    .queue_call(vec![28, true as u8], accounts, false, &timelock)
+    .await
+    .unwrap();
+  assert_eq!(
+    env.execute_action(&timelock.pubkey()).await,
+    Err(app_error(AppError::Timelocked))
+  );
+  assert!(!env.pool_data().await.is_permissioned);
+  env.skip_timelock().await;
+  env.execute_action(&timelock.pubkey()).await.unwrap();
+  assert!(env.pool_data().await.is_permissioned);
+  assert_eq!(
+    env.add_liquidity(1, 1, 1, env.treasuries).await,
+    Err(app_error(AppError::NotMember))
+  );
+  assert_eq!(
+    env.swap(1000, 0, 1, 0, s).await,
+    Err(app_error(AppError::NotMember))
+  );
+
+  assert_eq!(
+    env.add_member(&payer, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  env.add_member(&payer, false).await.unwrap();
+  assert_eq!(
+    env.add_member(&payer, false).await,
+    Err(app_error(AppError::ConstructorOnce))
+  );
+  env
+    .add_liquidity_with(1, 1, 1, env.treasuries, &[entry])
+    .await
+    .unwrap();
+  env
+    .swap_with(1000, 0, 1, 0, s, &[(WITH_MEMBER, entry)])
+    .await
+    .unwrap();
+  // The entry of another member does not qualify
+  let stranger = env.stranger.pubkey();
+  env.add_member(&stranger, false).await.unwrap();
+  assert_eq!(
+    env
+      .swap_with(1000, 0, 1, 0, s, &[(WITH_MEMBER, env.member(&stranger))])
+      .await,
+    Err(app_error(AppError::NotMember))
+  );
+
+  // Removed members can still exit
+  env.remove_member(&payer, false).await.unwrap();
+  assert_eq!(
+    env
+      .swap_with(1000, 0, 0, 1, s, &[(WITH_MEMBER, entry)])
+      .await,
+    Err(app_error(AppError::NotMember))
+  );
+  env.remove_liquidity(1, env.treasuries).await.unwrap();
+  env.update_permission(false, false).await.unwrap();
+  env.swap(1000, 0, 0, 1, s).await.unwrap();
+}
+
+#[tokio::test]
+async fn add_member_survives_a_prefunded_entry() {
+  let mut env = Env::initialized().await;
+  let payer = env.payer();
+  let entry = env.member(&payer);
+  // Anyone may send lamports to the entry address beforehand
+  let rent = env.context.banks_client.get_rent().await.unwrap();
+  let ix = system_instruction::transfer(&payer, &entry, rent.minimum_balance(0));
+  env.execute(&[ix], &[]).await.unwrap();
+  env.add_member(&payer, false).await.unwrap();
+  env.update_permission(true, false).await.unwrap();
+  env
+    .swap_with(1000, 0, 1, 0, env.treasuries[0], &[(WITH_MEMBER, entry)])
+    .await
+    .unwrap();
+  // Removing refunds the rent, adding again recreates the entry
+  env.remove_member(&payer, false).await.unwrap();
+  assert!(env
+    .context
+    .banks_client
+    .get_account(entry)
+    .await
+    .unwrap()
+    .is_none());
+  env.add_member(&payer, false).await.unwrap();
+  env
+    .swap_with(1000, 0, 1, 0, env.treasuries[0], &[(WITH_MEMBER, entry)])
+    .await
+    .unwrap();
+}
+
+///
+/// LockLiquidity & UnlockLiquidity
+///
+
+#[tokio::test]
+async fn lock_liquidity_rejects_zero_and_past_unlocks() {
+  let mut env = Env::initialized().await;
+  let now = env.now().await;
+  assert_eq!(
+    env
+      .lock_liquidity(0, now + 100, LockSchedule::Cliff)
+      .await
+      .err(),
+    Some(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.lock_liquidity(1, now, LockSchedule::Cliff).await.err(),
+    Some(app_error(AppError::InvalidSchedule))
+  );
+  // An unknown schedule is not an instruction
+  let mut data = pack(26, &[1, (now + 100) as u64]);
+  data.push(2);
+  assert_eq!(
+    env.call(data, vec![], &[]).await,
+    Err(app_error(AppError::InvalidInstruction))
+  );
+}
+
+#[tokio::test]
+async fn cliff_lock_releases_everything_at_the_unlock() {
+  let mut env = Env::initialized().await;
+  let lpt = env.balance(&env.lpt.clone()).await;
+  let now = env.now().await;
+  let locking = env
+    .lock_liquidity(lpt / 2, now + 1000, LockSchedule::Cliff)
+    .await
+    .unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt - lpt / 2);
+  assert_eq!(env.balance(&locking.escrow.pubkey()).await, lpt / 2);
+  env.warp(999).await;
+  assert_eq!(
+    env.unlock_liquidity(&locking, false).await,
+    Err(app_error(AppError::LockedLiquidity))
+  );
+  env.warp(1).await;
+  assert_eq!(
+    env.unlock_liquidity(&locking, true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  env.unlock_liquidity(&locking, false).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt);
+  assert_eq!(
+    env.unlock_liquidity(&locking, false).await,
+    Err(app_error(AppError::LockedLiquidity))
+  );
+}
+
+#[tokio::test]
+async fn linear_lock_releases_pro_rata() {
+  let mut env = Env::initialized().await;
+  let lpt = env.balance(&env.lpt.clone()).await;
+  let now = env.now().await;
+  let locking = env
+    .lock_liquidity(lpt, now + 1000, LockSchedule::Linear)
+    .await
+    .unwrap();
+  env.warp(250).await;
+  env.unlock_liquidity(&locking, false).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt / 4);
+  env.warp(250).await;
+  env.unlock_liquidity(&locking, false).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt / 2);
+  // Everything is released past the unlock
+  env.warp(10000).await;
+  env.unlock_liquidity(&locking, false).await.unwrap();
+  assert_eq!(env.balance(&env.lpt.clone()).await, lpt);
+  assert_eq!(env.balance(&locking.escrow.pubkey()).await, 0);
+}
+
+///
+/// Sync, Skim & Donate
+///
+
+#[tokio::test]
+async fn sync_absorbs_direct_transfers() {
+  let mut env = Env::initialized().await;
+  let [s, a, b] = env.treasuries;
+  env.gift(1, RESERVE / 10).await;
+  assert_eq!(
+    env.sync([s, b, a]).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  env.sync(env.treasuries).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_s, RESERVE);
+  assert_eq!(pool_data.reserve_a, RESERVE + RESERVE / 10);
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(
+    env.sync(env.treasuries).await,
+    Err(app_error(AppError::FrozenPool))
+  );
+}
+
+#[tokio::test]
+async fn skim_withdraws_the_excess() {
+  let mut env = Env::initialized().await;
+  let treasury_b = env.treasuries[2];
+  assert_eq!(env.skim(false).await, Err(app_error(AppError::ZeroValue)));
+  env.gift(2, RESERVE / 10).await;
+  assert_eq!(env.skim(true).await, Err(app_error(AppError::InvalidOwner)));
+  env.skim(false).await.unwrap();
+  assert_eq!(env.balance(&treasury_b).await, RESERVE);
+  assert_eq!(env.pool_data().await.reserve_b, RESERVE);
+}
+
+#[tokio::test]
+async fn donate_grows_reserves_without_minting() {
+  let mut env = Env::initialized().await;
+  let [s, a, b] = env.treasuries;
+  env.donate(0, RESERVE, 0, env.treasuries).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.reserve_a, 2 * RESERVE);
+  assert_eq!(env.supply().await, RESERVE);
+  assert_eq!(
+    env.donate(0, 0, 0, env.treasuries).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env.donate(1, 1, 1, [s, b, a]).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  env.freeze_pool(false).await.unwrap();
+  assert_eq!(
+    env.donate(1, 1, 1, env.treasuries).await,
+    Err(app_error(AppError::FrozenPool))
+  );
+}
+
+///
+/// ClosePool & ReseedPool
+///
+
+#[tokio::test]
+async fn close_pool_once_drained() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.close_pool(false).await,
+    Err(app_error(AppError::UndrainedPool))
+  );
+  env.drain().await;
+  assert_eq!(
+    env.close_pool(true).await,
+    Err(app_error(AppError::InvalidOwner))
+  );
+  // Only the account which locked the minimum liquidity counts
+  let burn = env.burn;
+  env.burn = env.lpt;
+  assert_eq!(
+    env.close_pool(false).await,
+    Err(app_error(AppError::UnmatchedPool))
+  );
+  env.burn = burn;
+  env.close_pool(false).await.unwrap();
+  let pool = env.pool.pubkey();
+  assert!(env
+    .context
+    .banks_client
+    .get_account(pool)
+    .await
+    .unwrap()
+    .is_none());
+  for treasury in env.treasuries.clone().iter().chain([env.burn].iter()) {
+    assert!(env
+      .context
+      .banks_client
+      .get_account(*treasury)
+      .await
+      .unwrap()
+      .is_none());
+  }
+  assert_eq!(env.supply().await, 0);
+}
+
+#[tokio::test]
+async fn close_pool_leaves_shared_earnings_to_the_split() {
+  let mut env = Env::initialized().await;
+  let treasury_s = env.treasuries[0];
+  env.swap(RESERVE / 100, 1, 1, 2, treasury_s).await.unwrap();
+  let split = Keypair::new();
+  let beneficiary = env.open_account(&Pubkey::new_unique(), 0).await;
+  env
+    .set_split(&split, &[DECIMALS], &[beneficiary], false)
+    .await
+    .unwrap();
+  env.drain().await;
+  assert_eq!(
+    env.close_pool(false).await,
+    Err(app_error(AppError::SharedVault))
+  );
+  env
+    .distribute_earnings(&split.pubkey(), &[beneficiary])
+    .await
+    .unwrap();
+  env.close_pool(false).await.unwrap();
+}
+
+#[tokio::test]
+async fn reseed_pool_once_drained() {
+  let mut env = Env::initialized().await;
+  assert_eq!(
+    env.reseed_pool(RESERVE, RESERVE, RESERVE).await,
+    Err(app_error(AppError::UndrainedPool))
+  );
+  env.drain().await;
+  assert_eq!(
+    env.reseed_pool(RESERVE, 0, RESERVE).await,
+    Err(app_error(AppError::ZeroValue))
+  );
+  assert_eq!(
+    env
+      .reseed_pool(RESERVE, minimum_reserve(9, RESERVE_PRECISION) - 1, RESERVE)
+      .await,
+    Err(app_error(AppError::InsufficientLiquidity))
+  );
+  env.reseed_pool(RESERVE, RESERVE, RESERVE).await.unwrap();
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.state, PoolState::Initialized);
+  assert!(pool_data.reserve_s >= RESERVE);
+  assert_eq!(env.supply().await, pool_data.reserve_s);
+}
+
+///
+/// MigratePool
+///
+
+#[tokio::test]
+async fn migrate_pool_upgrades_the_unversioned_layout() {
+  let mut env = Env::initialized().await;
+  let pool_data = env.pool_data().await;
+  assert_eq!(pool_data.version, POOL_VERSION);
+  // The current layout is not migrated again
+  let burn = Keypair::new();
+  assert_eq!(
+    env.migrate_pool(&burn, false).await,
+    Err(TransactionError::InstructionError(
+      0,
+      InstructionError::InvalidAccountData
+    ))
+  );
+  env.downgrade_pool().await;
+  // The unversioned pool is unreadable until migrated
+  let treasury_s = env.treasuries[0];
+  assert_eq!(
+    env.swap(RESERVE / 10, 1, 1, 2, treasury_s).await,
+    Err(TransactionError::InstructionError(
+      0,
+      InstructionError::InvalidAccountData
+    ))
+  );
+  // The burn account can't be one already holding LPT
+  let lpt = Keypair::new();
+  let ix = spl_token::instruction::initialize_account(
+    &spl_token::id(),
+    &lpt.pubkey(),
+    &env.mint_lpt.pubkey(),
+    &env.treasurer,
+  )
+  .unwrap();
+  env.allocate(&lpt, Account::LEN, &spl_token::id()).await;
+  env.execute(&[ix], &[]).await.unwrap();
+  assert_eq!(
+    env.migrate_pool(&lpt, false).await,
+    Err(app_error(AppError::ConstructorOnce))
+  );
+  // Anyone may migrate, paying the extra rent
+  let payer = env.payer();
+  let stranger = env.stranger.pubkey();
+  let rent = env.context.banks_client.get_rent().await.unwrap();
+  let ix = system_instruction::transfer(&payer, &stranger, rent.minimum_balance(Pool::LEN));
+  env.execute(&[ix], &[]).await.unwrap();
+  env.migrate_pool(&burn, true).await.unwrap();
+  let migrated = env.pool_data().await;
+  assert_eq!(
+    migrated,
+    Pool {
+      fee_min: FEE,
+      fee_max: FEE,
+      burn: burn.pubkey(),
+      ..pool_data
+    }
+  );
+  assert_eq!(env.balance(&burn.pubkey()).await, 0);
+  env.swap(RESERVE / 10, 1, 1, 2, treasury_s).await.unwrap();
+}
+
+#[tokio::test]
+async fn migrated_pool_is_drained_by_the_burn_balance() {
+  let mut env = Env::initialized().await;
+  env.drain().await;
+  env.downgrade_pool().await;
+  let burn = Keypair::new();
+  env.migrate_pool(&burn, false).await.unwrap();
+  env.burn = burn.pubkey();
+  // The new burn account locks nothing, so the LPT left elsewhere keeps the pool undrained
+  assert_eq!(env.supply().await, MINIMUM_LIQUIDITY);
+  assert_eq!(
+    env.close_pool(false).await,
+    Err(app_error(AppError::UndrainedPool))
+  );
+  assert_eq!(
+    env.reseed_pool(RESERVE, RESERVE, RESERVE).await,
+    Err(app_error(AppError::UndrainedPool))
+  );
+}
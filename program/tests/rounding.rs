@@ -0,0 +1,83 @@
+use main::helper::{
+  invariant::Invariant,
+  oracle::{Oracle, FEE},
+};
+use proptest::prelude::*;
+
+const MIN_RESERVE: u64 = 1000000;
+const MAX_RESERVE: u64 = 1000000000000000000;
+
+proptest! {
+  #[test]
+  fn proportional_deposit_then_withdraw_never_returns_more(
+    reserve_s in MIN_RESERVE..MAX_RESERVE,
+    reserve_a in MIN_RESERVE..MAX_RESERVE,
+    reserve_b in MIN_RESERVE..MAX_RESERVE,
+    reserve_lpt in MIN_RESERVE..MAX_RESERVE,
+    ratio in 1u64..1000000000,
+  ) {
+    let delta_s = ((reserve_s as u128) * (ratio as u128) / 1000000000) as u64;
+    let delta_a = ((reserve_a as u128) * (ratio as u128) / 1000000000) as u64;
+    let delta_b = ((reserve_b as u128) * (ratio as u128) / 1000000000) as u64;
+    let (lpt, new_reserve_s, new_reserve_a, new_reserve_b) = Oracle::rake(
+      delta_s, delta_a, delta_b, reserve_s, reserve_a, reserve_b, reserve_lpt,
+    ).unwrap();
+    let (paid_s, paid_a, paid_b) = Oracle::unrake(
+      lpt, new_reserve_s, new_reserve_a, new_reserve_b, reserve_lpt + lpt,
+    ).unwrap();
+    prop_assert!(paid_s <= delta_s);
+    prop_assert!(paid_a <= delta_a);
+    prop_assert!(paid_b <= delta_b);
+  }
+
+  #[test]
+  fn deposit_then_withdraw_never_dilutes_the_pool(
+    reserve_s in MIN_RESERVE..MAX_RESERVE,
+    reserve_a in MIN_RESERVE..MAX_RESERVE,
+    reserve_b in MIN_RESERVE..MAX_RESERVE,
+    reserve_lpt in MIN_RESERVE..MAX_RESERVE,
+    delta_s in 0..MAX_RESERVE,
+    delta_a in 0..MAX_RESERVE,
+    delta_b in 0..MAX_RESERVE,
+  ) {
+    let result = Oracle::rake(
+      delta_s, delta_a, delta_b, reserve_s, reserve_a, reserve_b, reserve_lpt,
+    );
+    // Deposits too large for the pool are rejected as an overflow
+    prop_assume!(result.is_some());
+    let (lpt, new_reserve_s, new_reserve_a, new_reserve_b) = result.unwrap();
+    let new_reserve_lpt = reserve_lpt.checked_add(lpt);
+    prop_assume!(new_reserve_lpt.is_some());
+    let new_reserve_lpt = new_reserve_lpt.unwrap();
+    prop_assert!(Invariant::is_share_kept(
+      (reserve_s, reserve_a, reserve_b), reserve_lpt,
+      (new_reserve_s, new_reserve_a, new_reserve_b), new_reserve_lpt,
+    ));
+    let (paid_s, paid_a, paid_b) = Oracle::unrake(
+      lpt, new_reserve_s, new_reserve_a, new_reserve_b, new_reserve_lpt,
+    ).unwrap();
+    // The pool is back to the same LPT supply with no less liquidity
+    prop_assert!(Invariant::is_product_kept(
+      reserve_s, reserve_a, reserve_b,
+      new_reserve_s - paid_s, new_reserve_a - paid_a, new_reserve_b - paid_b,
+    ));
+  }
+
+  #[test]
+  fn swap_back_and_forth_never_returns_more(
+    bid_reserve in MIN_RESERVE..MAX_RESERVE,
+    ask_reserve in MIN_RESERVE..MAX_RESERVE,
+    amount in 1..MAX_RESERVE,
+  ) {
+    let new_bid_reserve = bid_reserve + amount;
+    if let Some((new_ask_reserve, paid_amount, _)) =
+      Oracle::curve_in_fee(new_bid_reserve, bid_reserve, ask_reserve, FEE, 0, true)
+    {
+      if let Some((_, returned_amount, _)) = Oracle::curve_in_fee(
+        new_ask_reserve + paid_amount, new_ask_reserve, new_bid_reserve, FEE, 0, true,
+      ) {
+        prop_assert!(returned_amount <= amount);
+      }
+    }
+  }
+}
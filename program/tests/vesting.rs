@@ -0,0 +1,42 @@
+use main::helper::vesting::Vesting;
+use proptest::prelude::*;
+
+const MAX_DURATION: i64 = 1000000000; // More than 30 years
+
+proptest! {
+  #[test]
+  fn cliff_releases_nothing_before_the_unlock(
+    amount in any::<u64>(),
+    unlock_ts in 1..MAX_DURATION,
+    now in 0..MAX_DURATION,
+  ) {
+    let vested = Vesting::cliff(amount, unlock_ts, now);
+    prop_assert_eq!(vested, if now < unlock_ts { 0 } else { amount });
+  }
+
+  #[test]
+  fn linear_never_exceeds_the_pro_rata(
+    amount in any::<u64>(),
+    start_ts in 0..MAX_DURATION,
+    duration in 1..MAX_DURATION,
+    elapsed in 0..MAX_DURATION,
+  ) {
+    let vested = Vesting::linear(amount, start_ts, start_ts + duration, start_ts + elapsed).unwrap();
+    let pro_rata = amount as u128 * elapsed.min(duration) as u128 / duration as u128;
+    prop_assert_eq!(vested as u128, pro_rata);
+    prop_assert!(vested <= amount);
+  }
+
+  #[test]
+  fn linear_is_monotonic(
+    amount in any::<u64>(),
+    duration in 1..MAX_DURATION,
+    earlier in 0..MAX_DURATION,
+    later in 0..MAX_DURATION,
+  ) {
+    let (earlier, later) = (earlier.min(later), earlier.max(later));
+    let vested_earlier = Vesting::linear(amount, 0, duration, earlier).unwrap();
+    let vested_later = Vesting::linear(amount, 0, duration, later).unwrap();
+    prop_assert!(vested_earlier <= vested_later);
+  }
+}